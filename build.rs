@@ -0,0 +1,71 @@
+// Generates a `#[test]` for every strategy of every group in
+// `src/benches.manifest`, which `bench.rs` includes, and a `#[bench]` as well
+// with the `nightly` feature, and a `strategies!` macro that hands the
+// sweeps each group's strategies. The cells themselves are run by `matrix.rs`.
+//
+// Also sets `cfg(simd)`, which the vector kernels are built under, on x86 and
+// x86-64 unless the `scalar` feature is on.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code, clippy::redundant_field_names)]
+#[path = "src/manifest.rs"]
+mod manifest;
+
+fn main() {
+    set_simd_cfg();
+
+    let path = "src/benches.manifest";
+    println!("cargo:rerun-if-changed={}", path);
+    println!("cargo:rerun-if-changed=src/manifest.rs");
+
+    let text = fs::read_to_string(path).expect("reading the benchmark manifest");
+    let manifest = manifest::parse(&text)
+        .unwrap_or_else(|e| panic!("{}: {}", path, e));
+
+    let benches = env::var_os("CARGO_FEATURE_NIGHTLY").is_some();
+    let mut out = String::new();
+    for group in &manifest.groups {
+        writeln!(out, "mod {} {{", group.name).unwrap();
+        writeln!(out, "    use super::*;").unwrap();
+        if benches {
+            for strategy in &group.strategies {
+                writeln!(out, "    #[bench] fn {}(b: &mut Bencher) {{ bench_cell(b, {:?}, {:?}) }}",
+                         strategy, group.name, strategy).unwrap();
+            }
+        }
+        writeln!(out, "    mod check {{").unwrap();
+        writeln!(out, "        use super::super::*;").unwrap();
+        for strategy in &group.strategies {
+            writeln!(out, "        #[test] fn {}() {{ check_cell({:?}, {:?}) }}",
+                     strategy, group.name, strategy).unwrap();
+        }
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+
+    // For the sweeps in `bench.rs`, which bench a group's strategies over
+    // generated text: `strategies!(group, m!(args))` calls `m!(args [strategies])`
+    if benches {
+        writeln!(out, "macro_rules! strategies {{").unwrap();
+        for group in &manifest.groups {
+            writeln!(out, "    ({}, $m:ident!($($args:tt)*)) => {{ $m!($($args)* [{}]); }};",
+                     group.name, group.strategies.join(" ")).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    }
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("manifest_benches.rs");
+    fs::write(dest, out).expect("writing the generated benches");
+}
+
+fn set_simd_cfg() {
+    println!("cargo:rustc-check-cfg=cfg(simd)");
+    let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    if (arch == "x86" || arch == "x86_64") && env::var_os("CARGO_FEATURE_SCALAR").is_none() {
+        println!("cargo:rustc-cfg=simd");
+    }
+}
@@ -0,0 +1,88 @@
+// Runs the manifest's strategies with their input starting at every offset
+// within a cache line, so strategies that use aligned loads can be compared
+// with ones that don't on equal terms.
+
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+use std::slice;
+use std::str;
+use std::time::Duration;
+
+use fixtures;
+use ladder;
+use manifest::Manifest;
+use matrix;
+
+pub const CACHE_LINE: usize = 64;
+
+// Only ever read as bytes, through the buffer's pointer
+#[allow(dead_code)]
+#[repr(align(64))]
+#[derive(Clone, Copy)]
+struct Line([u8; CACHE_LINE]);
+
+/// A byte buffer that starts on a cache line boundary.
+pub struct AlignedBuf {
+    lines: Vec<Line>,
+    len: usize,
+}
+
+impl AlignedBuf {
+    pub fn new(len: usize, fill: u8) -> AlignedBuf {
+        let lines = len.div_ceil(CACHE_LINE);
+        AlignedBuf { lines: vec![Line([fill; CACHE_LINE]); lines], len: len }
+    }
+
+    /// Copies `text` in at `offset` and returns the copy.
+    pub fn place(&mut self, offset: usize, text: &str) -> &str {
+        let copy = &mut self[offset..offset + text.len()];
+        copy.copy_from_slice(text.as_bytes());
+        str::from_utf8(copy).expect("copied from a str")
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.lines.as_ptr() as *const u8, self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.lines.as_mut_ptr() as *mut u8, self.len) }
+    }
+}
+
+/// Times every strategy of every group over its fixture placed at each
+/// offset from 0 to 63, one row per run.
+pub fn report<W: Write>(manifest: &Manifest, budget: Duration, mut out: W) -> io::Result<()> {
+    writeln!(out, "{:<36} {:<24} {:>6} {:>14} {:>8}", "group", "strategy", "offset", "ns/op", "GB/s")?;
+    for group in &manifest.groups {
+        let fixture = fixtures::fixture(&group.fixture).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("no fixture named {}", group.fixture))
+        })?;
+        let mut buf = AlignedBuf::new(CACHE_LINE + fixture.len(), 0);
+
+        for strategy in &group.strategies {
+            for offset in 0..CACHE_LINE {
+                let text = buf.place(offset, fixture);
+                let mut run = match matrix::prepare_text(group, strategy, text) {
+                    Ok(Some(run)) => run,
+                    Ok(None) => break,
+                    Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
+                };
+                write!(out, "{:<36} {:<24} {:>6} ", group.name, strategy, offset)?;
+                let outcome = run();
+                if !outcome.satisfies(group.expect) {
+                    writeln!(out, "wrong: {:?} instead of {:?}", outcome, group.expect)?;
+                    continue;
+                }
+                let timing = ladder::time(&mut run, text.len(), budget);
+                writeln!(out, "{:>14.1} {:>8.2}", timing.ns_per_op(), timing.gb_per_s())?;
+            }
+        }
+    }
+    Ok(())
+}
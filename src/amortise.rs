@@ -0,0 +1,128 @@
+// Splits what a searcher costs into building it, calling it, and each byte it
+// searches, to find how long a haystack has to be before building a searcher
+// up front beats searching without one.
+//
+// Strategies that prepare nothing when built, like `twoway`, do their
+// preparation on every call instead, so it shows up in their call cost.
+
+use std::io::{self, Write};
+
+use byteset;
+use harness::{self, Config};
+use ladder;
+use manifest::{Group, Manifest, Search};
+use matrix::{self, lookup};
+use substring;
+
+/// The one-shot searches the others are measured against, where the group
+/// has them.
+pub static ONE_SHOT: &[&str] = &["multi_memchr", "find_std", "memchr"];
+
+// The haystack sizes: the call cost is the time over a tiny one, and the time
+// per byte comes from the difference between the other two
+const TINY: usize = 16;
+const SHORT: usize = 4 << 10;
+const LONG: usize = 64 << 10;
+
+/// What one search costs, in nanoseconds.
+#[derive(Clone, Copy, Debug)]
+pub struct Cost {
+    pub build: f64,
+    pub call: f64,
+    pub per_byte: f64,
+}
+
+impl Cost {
+    /// Building the searcher and searching `len` bytes with it once.
+    pub fn once(&self, len: f64) -> f64 {
+        self.build + self.call + self.per_byte * len
+    }
+}
+
+/// The haystack length from which `prebuilt` is cheaper than `one_shot`,
+/// counting both builds, or `None` if it never is.
+pub fn break_even(prebuilt: &Cost, one_shot: &Cost) -> Option<f64> {
+    let fixed = prebuilt.once(0.0) - one_shot.once(0.0);
+    let saved_per_byte = one_shot.per_byte - prebuilt.per_byte;
+    if saved_per_byte > 0.0 {
+        Some((fixed / saved_per_byte).max(0.0))
+    } else if fixed < 0.0 && saved_per_byte == 0.0 {
+        Some(0.0)
+    } else {
+        None
+    }
+}
+
+/// Measures `strategy` searching for what `group` looks for, or returns
+/// `None` if the group isn't a search, the strategy can't run here, or it
+/// only says whether there's a match and so has nothing to build.
+pub fn cost(group: &Group, strategy: &str, config: &Config) -> Result<Option<Cost>, String> {
+    let unknown = || format!("group {} has no strategy named {}", group.name, strategy);
+    if !matrix::built(strategy) {
+        return Ok(None);
+    }
+    let texts = [ladder::text(group, TINY), ladder::text(group, SHORT), ladder::text(group, LONG)];
+
+    match group.search {
+        Search::ByteSet(ref set) => {
+            let build = lookup(byteset::STRATEGIES, strategy).ok_or_else(unknown)?;
+            let searcher = match build(set) {
+                Some(searcher) => searcher,
+                None => return Ok(None),
+            };
+            let build = harness::measure(config, || build(set)).mean;
+            Ok(Some(split(build, config, &texts, |text| searcher.find(text))))
+        }
+        Search::Substring(_) if lookup(substring::CONTAINS, strategy).is_some() => Ok(None),
+        Search::Substring(ref needle) => {
+            let build = lookup(substring::STRATEGIES, strategy).ok_or_else(unknown)?;
+            let searcher = build(needle);
+            let build = harness::measure(config, || build(needle)).mean;
+            Ok(Some(split(build, config, &texts, |text| searcher.find(text))))
+        }
+        Search::IsAscii | Search::Lines => Ok(None),
+    }
+}
+
+fn split<F: Fn(&str) -> Option<usize>>(build: f64, config: &Config, texts: &[String; 3], find: F) -> Cost {
+    let ns: Vec<f64> = texts.iter().map(|text| harness::measure(config, || find(text)).mean).collect();
+    let per_byte = ((ns[2] - ns[1]) / (texts[2].len() - texts[1].len()) as f64).max(0.0);
+    Cost {
+        build: build,
+        call: (ns[0] - per_byte * texts[0].len() as f64).max(0.0),
+        per_byte: per_byte,
+    }
+}
+
+/// Prints the cost of every searcher in the manifest, and for each one that
+/// isn't one-shot, the haystack length at which it beats each one-shot
+/// search in its group.
+pub fn report<W: Write>(manifest: &Manifest, config: &Config, mut out: W) -> io::Result<()> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidInput, e);
+    writeln!(out, "{:<36} {:<16} {:>10} {:>10} {:>10}  break-even bytes",
+             "group", "strategy", "build ns", "call ns", "ns/KiB")?;
+
+    for group in &manifest.groups {
+        let mut costs = Vec::new();
+        for strategy in &group.strategies {
+            if let Some(cost) = cost(group, strategy, config).map_err(&invalid)? {
+                costs.push((&strategy[..], cost));
+            }
+        }
+
+        for &(strategy, ref cost) in &costs {
+            write!(out, "{:<36} {:<16} {:>10.1} {:>10.1} {:>10.1}",
+                   group.name, strategy, cost.build, cost.call, cost.per_byte * 1024.0)?;
+            if !ONE_SHOT.contains(&strategy) {
+                for &(one_shot, ref one_shot_cost) in costs.iter().filter(|c| ONE_SHOT.contains(&c.0)) {
+                    match break_even(cost, one_shot_cost) {
+                        Some(len) => write!(out, "  vs {}: {:.0}", one_shot, len)?,
+                        None => write!(out, "  vs {}: never", one_shot)?,
+                    }
+                }
+            }
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,323 @@
+// Picks the fastest kernel for each job on the machine it's running on, by
+// timing the candidates over a sample buffer, instead of trusting what was
+// fastest on the machine they were written on. Nothing changes unless
+// `autotune` is called; until then the dispatchers keep their defaults.
+//
+// The choices can be saved to a file, one line per CPU model, so later runs
+// on the same kind of machine can skip the timing.
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use byteset;
+use corpus::{self, Spec};
+use harness::{self, Config};
+use matrix::lookup;
+use newlines;
+
+/// A vector extension for a kernel to use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Avx2,
+    Sse2,
+    Sse,
+    Scalar,
+}
+
+static LEVELS: &[(&str, Level)] = &[
+    ("avx2", Level::Avx2),
+    ("sse2", Level::Sse2),
+    ("sse", Level::Sse),
+    ("scalar", Level::Scalar),
+];
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        LEVELS.iter().find(|l| l.1 == self).unwrap().0
+    }
+
+    pub fn from_name(name: &str) -> Option<Level> {
+        lookup(LEVELS, name)
+    }
+
+    /// True if the CPU running this has the extension.
+    #[cfg(simd)]
+    pub fn available(self) -> bool {
+        match self {
+            Level::Avx2 => is_x86_feature_detected!("avx2"),
+            Level::Sse2 => is_x86_feature_detected!("sse2"),
+            Level::Sse => is_x86_feature_detected!("sse"),
+            Level::Scalar => true,
+        }
+    }
+
+    /// True if the CPU running this has the extension.
+    #[cfg(not(simd))]
+    pub fn available(self) -> bool {
+        self == Level::Scalar
+    }
+}
+
+/// The levels `is_ascii_simd3` can run at.
+pub static IS_ASCII_LEVELS: &[Level] = &[Level::Avx2, Level::Sse2, Level::Sse, Level::Scalar];
+
+/// The levels the newline kernels can run at.
+pub static NEWLINE_LEVELS: &[Level] = &[Level::Avx2, Level::Sse2, Level::Scalar];
+
+/// The `byteset` strategies `byteset::build` can pick from: the ones that
+/// work for most sets, since `build` falls back to `position_table` for a
+/// set the tuned one can't take. `multi_memchr` and `jump_table` only work
+/// for a few. `sse2_cmpeq` takes the same up to 16 bytes as jetscii but only
+/// needs SSE2, so it's worth timing where jetscii falls back for want of
+/// SSE 4.2.
+pub static BYTE_SET_STRATEGIES: &[&str] = &[
+    "jetscii_bytes", "jetscii_ascii", "open_table", "position_table", "sse2_cmpeq",
+];
+
+// The dispatch tables. Each holds one more than the index of the choice in
+// its list of candidates, or 0 while it's untuned.
+static IS_ASCII: AtomicUsize = AtomicUsize::new(0);
+static NEWLINES: AtomicUsize = AtomicUsize::new(0);
+static BYTE_SET: AtomicUsize = AtomicUsize::new(0);
+
+fn get<T: Copy>(table: &AtomicUsize, candidates: &[T]) -> Option<T> {
+    match table.load(Ordering::Relaxed) {
+        0 => None,
+        i => Some(candidates[i - 1]),
+    }
+}
+
+fn set<T: PartialEq>(table: &AtomicUsize, candidates: &[T], choice: &T) {
+    let i = candidates.iter().position(|c| c == choice).expect("not one of the candidates");
+    table.store(i + 1, Ordering::Relaxed);
+}
+
+/// The level `is_ascii_simd3` was tuned to, if it has been.
+#[inline]
+pub fn is_ascii_level() -> Option<Level> {
+    get(&IS_ASCII, IS_ASCII_LEVELS)
+}
+
+/// The level the newline kernels were tuned to, if they have been.
+#[inline]
+pub fn newline_level() -> Option<Level> {
+    get(&NEWLINES, NEWLINE_LEVELS)
+}
+
+/// The strategy `byteset::build` was tuned to, if it has been.
+#[inline]
+pub fn byte_set_strategy() -> Option<&'static str> {
+    get(&BYTE_SET, BYTE_SET_STRATEGIES)
+}
+
+/// A choice for each dispatch table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Choices {
+    pub is_ascii: Level,
+    pub newlines: Level,
+    pub byte_set: &'static str,
+}
+
+/// Makes the dispatchers use `choices`.
+///
+/// # Panics
+///
+/// If a choice isn't one of its table's candidates, or is a level the CPU
+/// doesn't have.
+pub fn install(choices: &Choices) {
+    assert!(choices.is_ascii.available() && choices.newlines.available(),
+            "the CPU doesn't have the levels in {:?}", choices);
+    set(&IS_ASCII, IS_ASCII_LEVELS, &choices.is_ascii);
+    set(&NEWLINES, NEWLINE_LEVELS, &choices.newlines);
+    set(&BYTE_SET, BYTE_SET_STRATEGIES, &choices.byte_set);
+}
+
+/// Puts the dispatchers back to their defaults.
+pub fn reset() {
+    for table in &[&IS_ASCII, &NEWLINES, &BYTE_SET] {
+        table.store(0, Ordering::Relaxed);
+    }
+}
+
+// Long enough to measure the loops rather than the calls, and short enough to
+// stay in L2
+const SAMPLE_LEN: usize = 64 << 10;
+
+/// How long `autotune` gives each candidate: a fraction of a second for the
+/// lot.
+pub fn quick_config() -> Config {
+    Config {
+        warmup: Duration::from_millis(5),
+        measurement: Duration::from_millis(25),
+        samples: 10,
+        ..Config::default()
+    }
+}
+
+/// Times every candidate the CPU can run, and installs the fastest for each
+/// table.
+pub fn autotune() -> Choices {
+    autotune_with(&quick_config())
+}
+
+/// `autotune`, giving each candidate `config`.
+pub fn autotune_with(config: &Config) -> Choices {
+    let choices = measure(config);
+    install(&choices);
+    choices
+}
+
+/// Times every candidate the CPU can run, without installing the winners.
+pub fn measure(config: &Config) -> Choices {
+    // ASCII lines with none of the set in them, so each kernel reads all of it
+    let spec = Spec { size: SAMPLE_LEN, ..Spec::default() };
+    let sample = corpus::generate(&spec, 0);
+    let bytes = sample.as_bytes();
+    let set = byteset::MARKDOWN_SPECIAL;
+
+    let time = |f: &mut dyn FnMut()| harness::measure(config, f).mean;
+    let fastest = |times: Vec<(f64, usize)>| {
+        times.into_iter().min_by(|a, b| a.0.partial_cmp(&b.0).unwrap()).unwrap().1
+    };
+
+    let is_ascii = fastest(IS_ASCII_LEVELS.iter().enumerate()
+        .filter(|&(_, level)| level.available())
+        .map(|(i, &level)| (time(&mut || { harness::black_box(unsafe { ::is_ascii_simd3_at(level, bytes) }); }), i))
+        .collect());
+    let newlines = fastest(NEWLINE_LEVELS.iter().enumerate()
+        .filter(|&(_, level)| level.available())
+        .map(|(i, &level)| (time(&mut || { harness::black_box(unsafe { newlines::count_newlines_at(level, bytes) }); }), i))
+        .collect());
+    let byte_set = fastest(BYTE_SET_STRATEGIES.iter().enumerate()
+        .filter_map(|(i, &name)| lookup(byteset::STRATEGIES, name).and_then(|build| build(set)).map(|s| (i, s)))
+        .map(|(i, searcher)| (time(&mut || { harness::black_box(searcher.find(&sample)); }), i))
+        .collect());
+
+    Choices {
+        is_ascii: IS_ASCII_LEVELS[is_ascii],
+        newlines: NEWLINE_LEVELS[newlines],
+        byte_set: BYTE_SET_STRATEGIES[byte_set],
+    }
+}
+
+/// What the choices are saved under: the CPU's vendor, family and model, as
+/// in `GenuineIntel-6-85`.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn cpu_key() -> String {
+    // `__cpuid` is only unsafe on older compilers
+    #[allow(unused_unsafe)]
+    let (leaf0, eax) = unsafe {
+        use arch::__cpuid;
+        (__cpuid(0), __cpuid(1).eax)
+    };
+    let mut vendor = Vec::with_capacity(12);
+    for reg in &[leaf0.ebx, leaf0.edx, leaf0.ecx] {
+        for i in 0..4 {
+            vendor.push((reg >> (i * 8)) as u8);
+        }
+    }
+    key_for(&vendor, eax)
+}
+
+/// `cpu_key` for the vendor string from `cpuid` leaf 0 and the signature in
+/// `eax` of leaf 1. Some vendor strings have spaces in them, like
+/// `"  Shanghai  "`, so those are trimmed and the rest replaced, to keep the
+/// key one field of the saved choices.
+pub fn key_for(vendor: &[u8], eax: u32) -> String {
+    let vendor = String::from_utf8_lossy(vendor);
+    let vendor: Vec<&str> = vendor.split(|c: char| c.is_whitespace() || c == '\0')
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    // The extended fields only count in the families that ran out of room
+    // in the base ones
+    let base_family = (eax >> 8) & 0xf;
+    let mut family = base_family;
+    let mut model = (eax >> 4) & 0xf;
+    if base_family == 0xf {
+        family += (eax >> 20) & 0xff;
+    }
+    if base_family == 0x6 || base_family == 0xf {
+        model |= ((eax >> 16) & 0xf) << 4;
+    }
+    format!("{}-{}-{}", vendor.join("_"), family, model)
+}
+
+/// What the choices are saved under: the CPU's vendor, family and model, as
+/// in `GenuineIntel-6-85`.
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub fn cpu_key() -> String {
+    "unknown".to_string()
+}
+
+// The saved choices are a line per CPU, `key is_ascii newlines byte_set`
+const HEADER: &str = "# cpu is_ascii newlines byte_set";
+
+/// Parses a line of saved choices into the CPU key and the choices.
+pub fn parse_line(line: &str) -> Result<(&str, Choices), String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() != 4 {
+        return Err(format!("expected 4 fields, not {}: {}", fields.len(), line));
+    }
+    let level = |name| Level::from_name(name).ok_or_else(|| format!("no level named {}", name));
+    let byte_set = BYTE_SET_STRATEGIES.iter().find(|&&s| s == fields[3])
+        .ok_or_else(|| format!("no byte set strategy named {}", fields[3]))?;
+    Ok((fields[0], Choices { is_ascii: level(fields[1])?, newlines: level(fields[2])?, byte_set: byte_set }))
+}
+
+fn read(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+        r => r,
+    }
+}
+
+/// The choices saved in `path` for this CPU, or `None` if there are none, or
+/// their line doesn't parse, or they name a level this CPU doesn't have after
+/// all. Lines for other CPUs aren't looked at.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Option<Choices>> {
+    let key = cpu_key();
+    for line in read(path.as_ref())?.lines().filter(|l| !l.starts_with('#') && !l.trim().is_empty()) {
+        if line.split_whitespace().next() != Some(&key[..]) {
+            continue;
+        }
+        return Ok(match parse_line(line) {
+            Ok((_, choices)) if choices.is_ascii.available() && choices.newlines.available() => Some(choices),
+            _ => None,
+        });
+    }
+    Ok(None)
+}
+
+/// Saves `choices` in `path` for this CPU, keeping what's there for others.
+pub fn save<P: AsRef<Path>>(path: P, choices: &Choices) -> io::Result<()> {
+    let key = cpu_key();
+    let old = read(path.as_ref())?;
+    let mut text = String::new();
+    text.push_str(HEADER);
+    text.push('\n');
+    for line in old.lines().filter(|l| !l.starts_with('#') && !l.trim().is_empty()) {
+        if line.split_whitespace().next() != Some(&key[..]) {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+    text.push_str(&format!("{} {} {} {}\n",
+                           key, choices.is_ascii.name(), choices.newlines.name(), choices.byte_set));
+    fs::write(path, text)
+}
+
+/// Installs the choices saved in `path` for this CPU, or tunes and saves
+/// them there if there aren't any.
+pub fn load_or_autotune<P: AsRef<Path>>(path: P) -> io::Result<Choices> {
+    if let Some(choices) = load(&path)? {
+        install(&choices);
+        return Ok(choices);
+    }
+    let choices = autotune();
+    save(&path, &choices)?;
+    Ok(choices)
+}
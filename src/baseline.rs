@@ -0,0 +1,59 @@
+// What the hardware can do over a buffer without searching it, to measure the
+// strategies against. Reading every byte is the limit for anything that
+// looks at all of its input, and copying it is a familiar yardstick.
+
+use harness::black_box;
+
+/// The baselines, by name.
+pub static BASELINES: &[&str] = &["or_reduce", "memcpy"];
+
+/// The baseline the strategies are given as a percentage of.
+pub const SPEED_OF_LIGHT: &str = "or_reduce";
+
+multiversion! {
+    /// ORs every byte together. LLVM vectorises the loop with the widest
+    /// registers the CPU has, the same as the widest kernels use, and only
+    /// folds the lanes together at the end.
+    #[inline(never)]
+    pub fn or_reduce(bytes: &[u8]) -> u8 {
+        let mut lanes = [0u8; 64];
+        let mut chunks = bytes.chunks_exact(64);
+        for chunk in &mut chunks {
+            for (lane, &b) in lanes.iter_mut().zip(chunk) {
+                *lane |= b;
+            }
+        }
+        lanes.iter().chain(chunks.remainder()).fold(0, |acc, &b| acc | b)
+    }
+}
+
+/// A buffer of `len` bytes to time the baselines over where there's no text
+/// to hand. Not `vec![0; len]`: the kernel hands out zeroed memory as
+/// mappings of one shared zero page, which reads at L1 speed at any size.
+pub fn buffer(len: usize) -> Vec<u8> {
+    vec![b'a'; len]
+}
+
+/// Sets up a baseline to repeat over `bytes`, or returns `None` if there is
+/// no baseline called `name`.
+pub fn prepare<'a>(name: &str, bytes: &'a [u8]) -> Option<Box<dyn FnMut() + 'a>> {
+    match name {
+        "or_reduce" => Some(Box::new(move || {
+            black_box(or_reduce(black_box(bytes)));
+        })),
+        "memcpy" => {
+            let mut copy = vec![0; bytes.len()];
+            Some(Box::new(move || {
+                copy.copy_from_slice(black_box(bytes));
+                black_box(&mut copy[..]);
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// A strategy's speed as a percentage of the speed of light, from both
+/// their times over the same input.
+pub fn percent_of(baseline_ns: f64, ns: f64) -> f64 {
+    baseline_ns / ns * 100.0
+}
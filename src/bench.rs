@@ -1,13 +1,13 @@
 #![allow(warnings)]
 
+#[cfg(feature = "nightly")]
 use test::Bencher;
+#[cfg(feature = "nightly")]
 use test::black_box;
 use memchr::*;
 use jetscii::ByteSubstring;
 use super::*;
 
-static FORBIDDEN_CHARS: &[u8] = &[b'#', b'_', b'*', b'=', b'-', b'~', b'|', b'[', b'\\', b'>', b'^', b'`', b'&', b'/', b':', b'@'];
-
 static EXAMPLE_BIG: &str = include_str!("comrak-readme.md");
 static EXAMPLE_SIMPLE: &str = include_str!("simple.md");
 static EXAMPLE_WWW: &str = include_str!("www.md");
@@ -20,245 +20,88 @@ static EXAMPLE_LIPSUM_AT: &str = include_str!("lipsum-at.md");
 static EXAMPLE_UNICODE: &str = include_str!("unicode.md");
 static EXAMPLE_LATE_UNICODE: &str = include_str!("late-unicode.md");
 
-// The byte found here is near the front of the list of bytes, which is good for
-// the memchr searcher.
-mod find_set_of_bytes_early {
-
-    use super::*;
-
-    #[bench]
-    fn jetscii_bytes(b: &mut Bencher) {
-        let bytes = bytes!(b'#', b'_', b'*', b'=', b'-', b'~', b'|', b'[', b'\\', b'>', b'^', b'`', b'&', b'/', b':', b'@');
-        b.iter(|| {
-            let r = bytes.find(EXAMPLE_LIPSUM_EMPH.as_bytes());
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_EMPH.as_bytes()[r.unwrap()] as char, '_');
-            assert_eq!(r, Some(419));
-            black_box(r);
-        });
-    }
-
-    #[bench]
-    fn jetscii_ascii(b: &mut Bencher) {
-        let chars = ascii_chars!('#', '_', '*', '=', '-', '~', '|', '[', '\\', '>', '^', '`', '&', '/', ':', '@');
-        b.iter(|| {
-            let r = chars.find(EXAMPLE_LIPSUM_EMPH);
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_EMPH.as_bytes()[r.unwrap()] as char, '_');
-            assert_eq!(r, Some(419));
-            black_box(r);
-        });
-    }
-
-    #[bench]
-    fn open_table(b: &mut Bencher) {
-        let mut table: [bool; 256] = [false; 256];
-        for ch in FORBIDDEN_CHARS {
-            table[*ch as usize] = true;
-        }
-        let table = table;
-        b.iter(|| {
-            let mut r = None;
-            for (i, byte) in EXAMPLE_LIPSUM_EMPH.as_bytes().iter().enumerate() {
-                if table[*byte as usize] {
-                    r = Some(i);
-                    break;
-                }
-            }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_EMPH.as_bytes()[r.unwrap()] as char, '_');
-            assert_eq!(r, Some(419));
-            black_box(r);
-        });
-    }
-
-    #[bench]
-    fn position_table(b: &mut Bencher) {
-        let mut table: [bool; 256] = [false; 256];
-        for ch in FORBIDDEN_CHARS {
-            table[*ch as usize] = true;
-        }
-        let table = table;
-        b.iter(|| {
-            let r = EXAMPLE_LIPSUM_EMPH.as_bytes().iter().position(|byte| {
-                if table[*byte as usize] {
-                    true
-                } else {
-                    false
-                }
-            });
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_EMPH.as_bytes()[r.unwrap()] as char, '_');
-            assert_eq!(r, Some(419));
-            black_box(r);
-        });
-    }
+// The benches for every strategy in benches.manifest, and a test for each
+// that checks its result. The benches need the `nightly` feature.
+include!(concat!(env!("OUT_DIR"), "/manifest_benches.rs"));
 
-    #[bench]
-    fn multi_memchr(b: &mut Bencher) {
-        b.iter(|| {
-            let mut r = None;
-            for window in FORBIDDEN_CHARS.chunks(3) {
-                let r_ = match window {
-                    [a, b, c] => memchr3(*a, *b, *c, EXAMPLE_LIPSUM_EMPH.as_bytes()),
-                    [a, b] => memchr2(*a, *b, EXAMPLE_LIPSUM_EMPH.as_bytes()),
-                    [a] => memchr(*a, EXAMPLE_LIPSUM_EMPH.as_bytes()),
-                    _ => unreachable!(),
-                };
-                if r_.is_some() {
-                    r = r_;
-                    break;
-                }
-            }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_EMPH.as_bytes()[r.unwrap()] as char, '_');
-            assert_eq!(r, Some(419));
-            black_box(r);
-        });
+#[cfg(feature = "nightly")]
+fn bench_cell(b: &mut Bencher, group: &str, strategy: &str) {
+    let manifest = matrix::manifest();
+    let group = manifest.group(group).unwrap();
+    // Strategies that can't run on this machine measure nothing
+    let run = matrix::prepare(group, strategy).unwrap();
+    if let Some(mut run) = run {
+        b.iter(|| black_box(run()));
     }
+}
 
-    #[bench]
-    fn jump_table(b: &mut Bencher) {
-        b.iter(|| {
-            let mut r = None;
-            for (i, ch) in EXAMPLE_LIPSUM_EMPH.as_bytes().iter().enumerate() {
-                match ch {
-                    b'#' |  b'_' |  b'*' |  b'=' |  b'-' |  b'~' |  b'|' |  b'[' |
-                    b'\\' | b'>' |  b'^' |  b'`' |  b'&' |  b'/' |  b':' |  b'@' => {
-                        r = Some(i);
-                        break
-                    }
-                    _ => ()
-                }
-            }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_EMPH.as_bytes()[r.unwrap()] as char, '_');
-            assert_eq!(r, Some(419));
-            black_box(r);
-        });
+fn check_cell(group: &str, strategy: &str) {
+    let manifest = matrix::manifest();
+    if let Err(e) = matrix::check(manifest.group(group).unwrap(), strategy) {
+        panic!("{}", e);
     }
-    
 }
 
-// The byte found here is at the end of the list of 'forbidden bytes', which is
-// bad for the memchr searcher.
-mod find_set_of_bytes_late {
-
-    use super::*;
+// Every strategy over text from nothing to 64 MiB. It takes minutes, so run
+// it on its own:
+//
+//     cargo test --release size_ladder -- --ignored --nocapture
+#[test]
+#[ignore]
+fn size_ladder() {
+    let budget = ::std::time::Duration::from_millis(20);
+    ladder::report(&matrix::manifest(), ladder::SIZES, budget, ::std::io::stdout()).unwrap();
+}
 
-    #[bench]
-    fn jetscii_bytes(b: &mut Bencher) {
-        let bytes = bytes!(b'#', b'_', b'*', b'=', b'-', b'~', b'|', b'[', b'\\', b'>', b'^', b'`', b'&', b'/', b':', b'@');
-        b.iter(|| {
-            let r = bytes.find(EXAMPLE_LIPSUM_AT.as_bytes());
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_AT.as_bytes()[r.unwrap()] as char, '@');
-            assert_eq!(r, Some(613));
-            black_box(r);
-        });
-    }
+// Every strategy over its fixture at each offset within a cache line:
+//
+//     cargo test --release alignment_sweep -- --ignored --nocapture
+#[test]
+#[ignore]
+fn alignment_sweep() {
+    let budget = ::std::time::Duration::from_millis(20);
+    alignment::report(&matrix::manifest(), budget, ::std::io::stdout()).unwrap();
+}
 
-    #[bench]
-    fn jetscii_ascii(b: &mut Bencher) {
-        let chars = ascii_chars!('#', '_', '*', '=', '-', '~', '|', '[', '\\', '>', '^', '`', '&', '/', ':', '@');
-        b.iter(|| {
-            let r = chars.find(EXAMPLE_LIPSUM_AT);
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_AT.as_bytes()[r.unwrap()] as char, '@');
-            assert_eq!(r, Some(613));
-            black_box(r);
-        });
-    }
-    
-    #[bench]
-    fn open_table(b: &mut Bencher) {
-        let mut table: [bool; 256] = [false; 256];
-        for ch in FORBIDDEN_CHARS {
-            table[*ch as usize] = true;
-        }
-        let table = table;
-        b.iter(|| {
-            let mut r = None;
-            for (i, byte) in EXAMPLE_LIPSUM_EMPH.as_bytes().iter().enumerate() {
-                if table[*byte as usize] {
-                    r = Some(i);
-                    break;
-                }
-            }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_EMPH.as_bytes()[r.unwrap()] as char, '_');
-            assert_eq!(r, Some(419));
-            black_box(r);
-        });
-    }
+// Single calls, cold and on tiny inputs:
+//
+//     cargo test --release latency_ -- --ignored --nocapture
+#[test]
+#[ignore]
+fn latency_cold_cache() {
+    latency::report_cold(&matrix::manifest(), 10_000, ::std::io::stdout()).unwrap();
+}
 
-    #[bench]
-    fn position_table(b: &mut Bencher) {
-        let mut table: [bool; 256] = [false; 256];
-        for ch in FORBIDDEN_CHARS {
-            table[*ch as usize] = true;
-        }
-        let table = table;
-        b.iter(|| {
-            let r = EXAMPLE_LIPSUM_AT.as_bytes().iter().position(|byte| {
-                if table[*byte as usize] {
-                    true
-                } else {
-                    false
-                }
-            });
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_AT.as_bytes()[r.unwrap()] as char, '@');
-            assert_eq!(r, Some(613));
-            black_box(r);
-        });
-    }
+#[test]
+#[ignore]
+fn latency_tiny_inputs() {
+    latency::report_tiny(&matrix::manifest(), 100_000, ::std::io::stdout()).unwrap();
+}
 
-    #[bench]
-    fn multi_memchr(b: &mut Bencher) {
-        b.iter(|| {
-            let mut r = None;
-            for chunk in FORBIDDEN_CHARS.chunks(3) {
-                let r_ = match chunk {
-                    [a, b, c] => memchr3(*a, *b, *c, EXAMPLE_LIPSUM_AT.as_bytes()),
-                    [a, b] => memchr2(*a, *b, EXAMPLE_LIPSUM_AT.as_bytes()),
-                    [a] => memchr(*a, EXAMPLE_LIPSUM_AT.as_bytes()),
-                    _ => unreachable!(),
-                };
-                if r_.is_some() {
-                    r = r_;
-                    break;
-                }
-            }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_AT.as_bytes()[r.unwrap()] as char, '@');
-            assert_eq!(r, Some(613));
-            black_box(r);
-        });
-    }
+// What building each searcher costs, next to what searching with it costs:
+//
+//     cargo test --release amortisation -- --ignored --nocapture
+#[test]
+#[ignore]
+fn amortisation() {
+    let config = harness::Config {
+        warmup: ::std::time::Duration::from_millis(20),
+        measurement: ::std::time::Duration::from_millis(100),
+        ..harness::Config::default()
+    };
+    amortise::report(&matrix::manifest(), &config, ::std::io::stdout()).unwrap();
+}
 
-    #[bench]
-    fn jump_table(b: &mut Bencher) {
-        b.iter(|| {
-            let mut r = None;
-            for (i, ch) in EXAMPLE_LIPSUM_AT.as_bytes().iter().enumerate() {
-                match ch {
-                    b'#' |  b'_' |  b'*' |  b'=' |  b'-' |  b'~' |  b'|' |  b'[' |
-                    b'\\' | b'>' |  b'^' |  b'`' |  b'&' |  b'/' |  b':' |  b'@' => {
-                        r = Some(i);
-                        break
-                    }
-                    _ => ()
-                }
-            }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_LIPSUM_AT.as_bytes()[r.unwrap()] as char, '@');
-            assert_eq!(r, Some(613));
-            black_box(r);
-        });
-    }
+// What `autotune` would pick on this machine:
+//
+//     cargo test --release autotune_choices -- --ignored --nocapture
+#[test]
+#[ignore]
+fn autotune_choices() {
+    println!("{}: {:?}", autotune::cpu_key(), autotune::measure(&autotune::quick_config()));
 }
 
+#[cfg(feature = "nightly")]
 #[bench]
 fn jetscii_setup(b: &mut Bencher) {
     b.iter(|| {
@@ -267,809 +110,291 @@ fn jetscii_setup(b: &mut Bencher) {
     });
 }
 
-// Looking for a short substring that only appears once
-mod find_short_substring_easy {
+#[cfg(feature = "nightly")]
+mod lines {
 
     use super::*;
 
     #[bench]
-    fn find_std(b: &mut Bencher) {
+    fn memchr_byte_lines(b: &mut Bencher) {
         b.iter(|| {
-            let r = EXAMPLE_WWW.find("www.");
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(600));
-            black_box(r);
-        });
-    }
-
-    #[bench]
-    fn contains_std(b: &mut Bencher) {
-        b.iter(|| {
-            let r = EXAMPLE_WWW.contains("www.");
-            assert!(r);
-            black_box(r);
-        });
-    }
-    
-    #[bench]
-    fn jetscii(b: &mut Bencher) {
-        let sub = ByteSubstring::new("www.".as_bytes());
-        b.iter(|| {
-            let r = sub.find(EXAMPLE_WWW.as_bytes());
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(600));
-            black_box(r);
-        });
-    }
-
-    #[bench]
-    fn memchr_(b: &mut Bencher) {
-        let needle = "www.".as_bytes();
-        b.iter(|| {
-            let mut total_offset = 0;
-            let mut r = None;
-            let mut slice = EXAMPLE_WWW.as_bytes();
-            while let Some(i) = memchr(b'w', slice) {
-                assert_eq!(slice[i], b'w');
-                if slice.len() - i >= needle.len() {
-                    let subslice = &slice[i..i + needle.len()];
-                    total_offset += i;
-                    if subslice == needle {
-                        r = Some(total_offset);
-                        break;
-                    } else {
-                        total_offset += 1;
-                        slice = &slice[i + 1..];
-                    }
-                } else {
-                    break;
-                }
+            let mut lines = 0;
+            for line in fast_byte_lines(EXAMPLE_BIG.as_bytes()) {
+                lines += 1;
+                black_box(line);
             }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(600));
-            black_box(r);
+            assert_eq!(lines, 172);
+            black_box(lines);
         });
     }
 
     #[bench]
-    fn memchr_std(b: &mut Bencher) {
-        let needle = "www.".as_bytes();
+    fn memchr_byte_lines_lossy(b: &mut Bencher) {
         b.iter(|| {
-            let mut total_offset = 0;
-            let mut r = None;
-            let mut slice = EXAMPLE_WWW.as_bytes();
-            while let Some(i) = ::core::slice::memchr::memchr(b'w', slice) {
-                assert_eq!(slice[i], b'w');
-                if slice.len() - i >= needle.len() {
-                    let subslice = &slice[i..i + needle.len()];
-                    total_offset += i;
-                    if subslice == needle {
-                        r = Some(total_offset);
-                        break;
-                    } else {
-                        total_offset += 1;
-                        slice = &slice[i + 1..];
-                    }
-                } else {
-                    break;
-                }
+            let mut lines = 0;
+            for line in fast_byte_lines(EXAMPLE_BIG.as_bytes()).lossy() {
+                lines += 1;
+                black_box(line);
             }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(600));
-            black_box(r);
-        });
-    }
-    
-    #[bench]
-    fn twoway(b: &mut Bencher) {
-        b.iter(|| {
-            let r = twoway::find_bytes(EXAMPLE_WWW.as_bytes(), b"www.");
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(600));
-            black_box(r);
+            assert_eq!(lines, 172);
+            black_box(lines);
         });
     }
 
     #[bench]
-    fn bmh(b: &mut Bencher) {
+    fn count_lines(b: &mut Bencher) {
         b.iter(|| {
-            let r = twoway::bmh::find(EXAMPLE_WWW.as_bytes(), b"www.");
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(600));
-            black_box(r);
-        });
-    }
-    
-}
-
-// Looking for a short substring that has many false matches
-mod find_short_substring_pathological {
-
-    use super::*;
-
-    #[bench]
-    fn find_std(b: &mut Bencher) {
-        b.iter(|| {
-            let r = EXAMPLE_WWW2.find("www.");
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW2.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1233));
-            black_box(r);
+            let lines = super::count_lines(EXAMPLE_BIG.as_bytes());
+            assert_eq!(lines, 172);
+            black_box(lines);
         });
     }
 
     #[bench]
-    fn contains_std(b: &mut Bencher) {
+    fn count_lines_unterminated(b: &mut Bencher) {
+        let text = EXAMPLE_BIG.trim_end();
+        assert_eq!(fast_lines(text).count(), 172);
         b.iter(|| {
-            let r = EXAMPLE_WWW2.contains("www.");
-            assert!(r);
-            black_box(r);
-        });
-    }
-    
-    #[bench]
-    fn jetscii(b: &mut Bencher) {
-        let sub = ByteSubstring::new("www.".as_bytes());
-        b.iter(|| {
-            let r = sub.find(EXAMPLE_WWW2.as_bytes());
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW2.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1233));
-            black_box(r);
+            let lines = super::count_lines(text.as_bytes());
+            assert_eq!(lines, 172);
+            black_box(lines);
         });
     }
 
     #[bench]
-    fn memchr_(b: &mut Bencher) {
-        let needle = "www.".as_bytes();
+    fn nth_std(b: &mut Bencher) {
         b.iter(|| {
-            let mut total_offset = 0;
-            let mut r = None;
-            let mut slice = EXAMPLE_WWW2.as_bytes();
-            while let Some(i) = memchr(b'w', slice) {
-                assert_eq!(slice[i], b'w');
-                if slice.len() - i >= needle.len() {
-                    let subslice = &slice[i..i + needle.len()];
-                    total_offset += i;
-                    if subslice == needle {
-                        r = Some(total_offset);
-                        break;
-                    } else {
-                        total_offset += 1;
-                        slice = &slice[i + 1..];
-                    }
-                } else {
-                    break;
-                }
-            }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW2.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1233));
-            black_box(r);
+            let line = EXAMPLE_BIG.lines().nth(150);
+            assert_eq!(line, Some("* [ConnyOnny](https://github.com/ConnyOnny)"));
+            black_box(line);
         });
     }
 
     #[bench]
-    fn memchr_std(b: &mut Bencher) {
-        let needle = "www.".as_bytes();
-        b.iter(|| {
-            let mut total_offset = 0;
-            let mut r = None;
-            let mut slice = EXAMPLE_WWW2.as_bytes();
-            while let Some(i) = ::core::slice::memchr::memchr(b'w', slice) {
-                assert_eq!(slice[i], b'w');
-                if slice.len() - i >= needle.len() {
-                    let subslice = &slice[i..i + needle.len()];
-                    total_offset += i;
-                    if subslice == needle {
-                        r = Some(total_offset);
-                        break;
-                    } else {
-                        total_offset += 1;
-                        slice = &slice[i + 1..];
-                    }
-                } else {
-                    break;
-                }
-            }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW2.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1233));
-            black_box(r);
-        });
-    }
-    
-    #[bench]
-    fn twoway(b: &mut Bencher) {
+    fn nth_fast_lines(b: &mut Bencher) {
         b.iter(|| {
-            let r = twoway::find_bytes(EXAMPLE_WWW2.as_bytes(), b"www.");
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW2.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1233));
-            black_box(r);
+            let line = fast_lines(EXAMPLE_BIG).nth(150);
+            assert_eq!(line, Some("* [ConnyOnny](https://github.com/ConnyOnny)"));
+            black_box(line);
         });
     }
 
     #[bench]
-    fn bmh(b: &mut Bencher) {
+    fn last_fast_lines(b: &mut Bencher) {
         b.iter(|| {
-            let r = twoway::bmh::find(EXAMPLE_WWW2.as_bytes(), b"www.");
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW2.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1233));
-            black_box(r);
+            let line = fast_lines(EXAMPLE_BIG).last();
+            assert_eq!(line, EXAMPLE_BIG.lines().last());
+            black_box(line);
         });
     }
     
 }
 
-mod find_long_substring_pathological {
+#[cfg(feature = "nightly")]
+mod line_index {
 
     use super::*;
 
-    static s: &str = "w www w w wwww w. ww ww wwww www w w ww w w w w www ww..";
-
     #[bench]
-    fn find_std(b: &mut Bencher) {
+    fn new(b: &mut Bencher) {
         b.iter(|| {
-            let r = EXAMPLE_WWW3.find(s);
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW3.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1176));
-            black_box(r);
+            let index = LineIndex::new(EXAMPLE_BIG);
+            assert_eq!(index.line_count(), 173);
+            black_box(index);
         });
     }
 
     #[bench]
-    fn contains_std(b: &mut Bencher) {
+    fn line_col(b: &mut Bencher) {
+        let index = LineIndex::new(EXAMPLE_UNICODE);
+        let offset = EXAMPLE_UNICODE.rfind("Ipsum").unwrap();
+        let line = EXAMPLE_UNICODE[..offset].matches('\n').count();
+        let line_start = EXAMPLE_UNICODE[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let chars = EXAMPLE_UNICODE[line_start..offset].chars().count();
         b.iter(|| {
-            let r = EXAMPLE_WWW3.contains(s);
-            assert!(r);
-            black_box(r);
-        });
-    }
-    
-    #[bench]
-    fn jetscii(b: &mut Bencher) {
-        let sub = ByteSubstring::new(s.as_bytes());
-        b.iter(|| {
-            let r = sub.find(EXAMPLE_WWW3.as_bytes());
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW3.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1176));
-            black_box(r);
+            let pos = index.line_col(offset, ColumnUnit::Char);
+            assert_eq!(pos, Some(LineCol { line: line, col: chars }));
+            assert_eq!(index.offset(line, chars, ColumnUnit::Char), Some(offset));
+            black_box(pos);
         });
     }
 
     #[bench]
-    fn memchr_(b: &mut Bencher) {
-        let needle = s.as_bytes();
+    fn edit(b: &mut Bencher) {
+        let index = LineIndex::new(EXAMPLE_BIG);
+        let at = EXAMPLE_BIG.len() / 2;
+        let text = "ünï\ncödé\r\n";
+        let mut edited = EXAMPLE_BIG.to_string();
+        edited.insert_str(at, text);
+        let expected = LineIndex::new(&edited);
         b.iter(|| {
-            let mut total_offset = 0;
-            let mut r = None;
-            let mut slice = EXAMPLE_WWW3.as_bytes();
-            while let Some(i) = memchr(b'w', slice) {
-                assert_eq!(slice[i], b'w');
-                if slice.len() - i >= needle.len() {
-                    let subslice = &slice[i..i + needle.len()];
-                    total_offset += i;
-                    if subslice == needle {
-                        r = Some(total_offset);
-                        break;
-                    } else {
-                        total_offset += 1;
-                        slice = &slice[i + 1..];
-                    }
-                } else {
-                    break;
-                }
+            let mut index = index.clone();
+            index.edit(at..at, text);
+            for line in 0..expected.line_count() {
+                assert_eq!(index.line_range(line), expected.line_range(line));
             }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW3.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1176));
-            black_box(r);
+            index.edit(at..at + text.len(), "");
+            assert_eq!(index.line_count(), 173);
+            black_box(index);
         });
     }
-
-    #[bench]
-    fn memchr_std(b: &mut Bencher) {
-        let needle = s.as_bytes();
-        b.iter(|| {
-            let mut total_offset = 0;
-            let mut r = None;
-            let mut slice = EXAMPLE_WWW3.as_bytes();
-            while let Some(i) = ::core::slice::memchr::memchr(b'w', slice) {
-                assert_eq!(slice[i], b'w');
-                if slice.len() - i >= needle.len() {
-                    let subslice = &slice[i..i + needle.len()];
-                    total_offset += i;
-                    if subslice == needle {
-                        r = Some(total_offset);
-                        break;
-                    } else {
-                        total_offset += 1;
-                        slice = &slice[i + 1..];
-                    }
-                } else {
-                    break;
-                }
-            }
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW3.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1176));
-            black_box(r);
-        });
-    }
-    
-    #[bench]
-    fn twoway(b: &mut Bencher) {
-        b.iter(|| {
-            let r = twoway::find_bytes(EXAMPLE_WWW3.as_bytes(), s.as_bytes());
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW3.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1176));
-            black_box(r);
-        });
-    }
-
-    #[bench]
-    fn bmh(b: &mut Bencher) {
-        b.iter(|| {
-            let r = twoway::bmh::find(EXAMPLE_WWW3.as_bytes(), s.as_bytes());
-            assert!(r.is_some());
-            assert_eq!(EXAMPLE_WWW3.as_bytes()[r.unwrap()] as char, 'w');
-            assert_eq!(r, Some(1176));
-            black_box(r);
-        });
-    }
-    
 }
 
-mod is_ascii {
+#[cfg(feature = "nightly")]
+mod normalize_line_endings {
 
     use super::*;
+    use std::borrow::Cow;
 
     #[bench]
-    fn std_bytes_closure(b: &mut Bencher) {
+    fn no_cr(b: &mut Bencher) {
         b.iter(|| {
-            let is_ascii = EXAMPLE_LIPSUM.as_bytes().iter().all(|b| b.is_ascii());
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn std_bytes(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = EXAMPLE_LIPSUM.as_bytes().is_ascii();
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn simd(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_simd(EXAMPLE_LIPSUM.as_bytes());
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn simd2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_simd2(EXAMPLE_LIPSUM.as_bytes());
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn simd3(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_simd3(EXAMPLE_LIPSUM.as_bytes());
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn simd3_avx2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = unsafe { super::is_ascii_simd3_x86_64_avx2(EXAMPLE_LIPSUM.as_bytes()) };
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn simd3_sse2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = unsafe { super::is_ascii_simd3_x86_64_sse2(EXAMPLE_LIPSUM.as_bytes()) };
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-    
-    #[bench]
-    fn simd3_sse(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = unsafe { super::is_ascii_simd3_x86_64_sse(EXAMPLE_LIPSUM.as_bytes()) };
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn auto_simd_avx2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = unsafe { super::is_ascii_auto_simd(EXAMPLE_LIPSUM.as_bytes(), Accel::AVX2) };
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn auto_simd_sse2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = unsafe { super::is_ascii_auto_simd(EXAMPLE_LIPSUM.as_bytes(), Accel::SSE2) };
-            assert!(is_ascii);
-            black_box(is_ascii);
-        });
-    }
-    
-    #[bench]
-    fn auto_simd_sse(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_auto_simd(EXAMPLE_LIPSUM.as_bytes(), Accel::SSE);
-            assert!(is_ascii);
-            black_box(is_ascii);
+            let text = super::normalize_line_endings(EXAMPLE_BIG);
+            match text {
+                Cow::Borrowed(text) => assert_eq!(text, EXAMPLE_BIG),
+                Cow::Owned(_) => panic!("copied text without a CR"),
+            }
+            black_box(text);
         });
     }
 
     #[bench]
-    fn auto_simd_any(b: &mut Bencher) {
+    fn crlf(b: &mut Bencher) {
+        let crlf = EXAMPLE_BIG.replace("\n", "\r\n");
         b.iter(|| {
-            let is_ascii = super::is_ascii_auto_simd(EXAMPLE_LIPSUM.as_bytes(), Accel::Any);
-            assert!(is_ascii);
-            black_box(is_ascii);
+            let text = super::normalize_line_endings(&crlf);
+            assert_eq!(text, EXAMPLE_BIG);
+            assert!(fast_lines(&crlf).eq(fast_lines(&text)));
+            black_box(text);
         });
     }
 
     #[bench]
-    fn naive_uninlined(b: &mut Bencher) {
+    fn crlf_in_place(b: &mut Bencher) {
+        let crlf = EXAMPLE_BIG.replace("\n", "\r\n").into_bytes();
         b.iter(|| {
-            let is_ascii = super::is_ascii_naive_uninlined(EXAMPLE_LIPSUM.as_bytes());
-            assert!(is_ascii);
-            black_box(is_ascii);
+            let mut buf = crlf.clone();
+            super::normalize_line_endings_in_place(&mut buf);
+            assert_eq!(buf, EXAMPLE_BIG.as_bytes());
+            black_box(buf);
         });
     }
 
     #[bench]
-    fn naive_inlined(b: &mut Bencher) {
+    fn mixed(b: &mut Bencher) {
+        let mixed = EXAMPLE_BIG.replace(".\n", ".\r").replace("\n", "\r\n");
         b.iter(|| {
-            let is_ascii = super::is_ascii_naive_inlined(EXAMPLE_LIPSUM.as_bytes());
-            assert!(is_ascii);
-            black_box(is_ascii);
+            let text = super::normalize_line_endings(&mixed);
+            assert_eq!(text, EXAMPLE_BIG);
+            assert_eq!(fast_lines(&text).count(), 172);
+            black_box(text);
         });
     }
 }
 
-mod is_not_ascii {
+// The manifest's searches again, over generated text, moving one thing at a
+// time. Each point is a module with a bench per strategy of the group.
+#[cfg(feature = "nightly")]
+mod generated {
 
     use super::*;
-
-    #[bench]
-    fn std_bytes_closure(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = EXAMPLE_LATE_UNICODE.as_bytes().iter().all(|b| b.is_ascii());
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn std_bytes(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = EXAMPLE_LATE_UNICODE.as_bytes().is_ascii();
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn simd(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_simd(EXAMPLE_LATE_UNICODE.as_bytes());
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn simd2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_simd2(EXAMPLE_LATE_UNICODE.as_bytes());
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
-    }
-
-    #[bench]
-    fn simd3(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_simd3(EXAMPLE_LATE_UNICODE.as_bytes());
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
-    }
-    
-    #[bench]
-    fn simd3_avx2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = unsafe { super::is_ascii_simd3_x86_64_avx2(EXAMPLE_LATE_UNICODE.as_bytes()) };
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
+    use corpus::{self, Spec};
+    use byteset::ByteSet;
+
+    fn bench_generated(b: &mut Bencher, group: &str, strategy: &str, spec: &Spec) {
+        let text = corpus::generate(spec, 0);
+        let manifest = matrix::manifest();
+        let group = manifest.group(group).unwrap();
+        let expect = matrix::reference(&group.search, &text);
+        let run = matrix::prepare_text(group, strategy, &text).unwrap();
+        if let Some(mut run) = run {
+            let outcome = run();
+            assert!(outcome.satisfies(expect), "{}::{} produced {:?} instead of {:?}",
+                    group.name, strategy, outcome, expect);
+            b.bytes = text.len() as u64;
+            b.iter(|| black_box(run()));
+        }
     }
 
-    #[bench]
-    fn simd3_sse2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = unsafe { super::is_ascii_simd3_x86_64_sse2(EXAMPLE_LATE_UNICODE.as_bytes()) };
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
+    macro_rules! point {
+        ($group:expr, $spec:expr, [$($strategy:ident)*]) => {
+            $(
+                #[bench]
+                fn $strategy(b: &mut Bencher) {
+                    bench_generated(b, $group, stringify!($strategy), &$spec)
+                }
+            )*
+        }
     }
 
-    #[bench]
-    fn simd3_sse(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = unsafe { super::is_ascii_simd3_x86_64_sse(EXAMPLE_LATE_UNICODE.as_bytes()) };
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
-    }
-    
-    #[bench]
-    fn auto_simd_avx2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_auto_simd(EXAMPLE_LATE_UNICODE.as_bytes(), Accel::AVX2);
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
+    macro_rules! sweep {
+        ($name:ident, $group:ident, { $($point:ident: $spec:expr,)* }) => {
+            mod $name {
+                use super::*;
+                $(
+                    mod $point {
+                        use super::*;
+                        strategies!($group, point!(stringify!($group), $spec,));
+                    }
+                )*
+            }
+        }
     }
 
-    #[bench]
-    fn auto_simd_sse2(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_auto_simd(EXAMPLE_LATE_UNICODE.as_bytes(), Accel::SSE2);
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
-    }
-    
-    #[bench]
-    fn auto_simd_sse(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_auto_simd(EXAMPLE_LATE_UNICODE.as_bytes(), Accel::SSE);
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
-    }
+    const SIZE: usize = 64 * 1024;
 
-    #[bench]
-    fn auto_simd_any(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_auto_simd(EXAMPLE_LATE_UNICODE.as_bytes(), Accel::Any);
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
+    fn substring(near_miss_density: f64, first_byte_density: f64) -> Spec {
+        Spec {
+            size: SIZE,
+            needle: "www.".to_string(),
+            match_at: Some(SIZE - 64),
+            near_miss_density: near_miss_density,
+            set: ByteSet::new(b"w"),
+            set_density: first_byte_density,
+            ..Spec::default()
+        }
     }
 
-    #[bench]
-    fn naive_uninlined(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_naive_uninlined(EXAMPLE_LATE_UNICODE.as_bytes());
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
-    }
+    sweep!(substring_near_misses, find_short_substring_pathological, {
+        none: substring(0.0, 0.0),
+        sparse: substring(0.001, 0.0),
+        dense: substring(0.05, 0.0),
+        first_byte: substring(0.0, 0.2),
+    });
 
-    #[bench]
-    fn naive_inlined(b: &mut Bencher) {
-        b.iter(|| {
-            let is_ascii = super::is_ascii_naive_inlined(EXAMPLE_LATE_UNICODE.as_bytes());
-            assert!(!is_ascii);
-            black_box(is_ascii);
-        });
+    fn set_of_bytes(match_at: usize) -> Spec {
+        Spec { size: SIZE, needle: "@".to_string(), match_at: Some(match_at), ..Spec::default() }
     }
-}
-
-mod split_lines {
 
-    use super::*;
+    sweep!(set_of_bytes_match_offset, find_set_of_bytes_late, {
+        at_16: set_of_bytes(16),
+        at_1k: set_of_bytes(1024),
+        at_end: set_of_bytes(SIZE - 1),
+    });
 
-    #[bench]
-    fn std(b: &mut Bencher) {
-        b.iter(|| {
-            let mut lines = 0;
-            for line in EXAMPLE_BIG.lines() {
-                lines += 1;
-                black_box(line);
-            }
-            assert_eq!(lines, 172);
-            black_box(lines);
-        });
+    fn ascii(size: usize, non_ascii_density: f64) -> Spec {
+        Spec { size: size, non_ascii_density: non_ascii_density, ..Spec::default() }
     }
 
-    #[bench]
-    fn memchr2_(b: &mut Bencher) {
-        b.iter(|| {
-            let mut slice = EXAMPLE_BIG.as_bytes();
-            let mut line = &[][..];
-            let mut lines = 0;
-            while !slice.is_empty() {
-                if let Some(i) = memchr2(b'\r', b'\n', slice) {
-                    line = &slice[..i];
-                    if slice[i] == b'\r' && slice.len() > i && slice[i] == b'\n' {
-                        slice = &slice[i + 2..];
-                    } else if slice[i] == b'\n' {
-                        slice = &slice[i + 1..];
-                    }
-                } else {
-                    line = slice;
-                    slice = &[];
-                }
-                lines += 1;
-                black_box(line);
-            }
-            assert_eq!(lines, 172);
-            black_box(lines);
-        });
-    }
+    sweep!(is_ascii_size, is_ascii, {
+        size_16: ascii(16, 0.0),
+        size_1k: ascii(1024, 0.0),
+        size_64k: ascii(SIZE, 0.0),
+    });
 
-    #[bench]
-    fn memchr2_unchecked(b: &mut Bencher) {
-        b.iter(|| {
-            unsafe {
-                let mut slice = EXAMPLE_BIG.as_bytes();
-                let mut line = &[][..];
-                let mut lines = 0;
-                while !slice.is_empty() {
-                    if let Some(i) = memchr2(b'\r', b'\n', slice) {
-                        line = slice.get_unchecked(0..i);
-                        if slice.get_unchecked(i) == &b'\r' && slice.len() > i
-                            && slice.get_unchecked(i) == &b'\n'
-                        {
-                            slice = slice.get_unchecked(i + 2..slice.len());
-                        } else if slice.get_unchecked(i) == &b'\n' {
-                            slice = slice.get_unchecked(i + 1..slice.len());
-                        }
-                    } else {
-                        line = slice;
-                        slice = &[];
-                    }
-                    lines += 1;
-                    black_box(line);
-                }
-                assert_eq!(lines, 172);
-                black_box(lines);
-            }
-        });
-    }
+    sweep!(is_ascii_density, is_not_ascii, {
+        rare: ascii(SIZE, 0.0001),
+        some: ascii(SIZE, 0.001),
+        common: ascii(SIZE, 0.05),
+    });
 
-    #[bench]
-    fn memchr_(b: &mut Bencher) {
-        b.iter(|| {
-            let mut slice = EXAMPLE_BIG.as_bytes();
-            let mut line = &[][..];
-            let mut lines = 0;
-            while !slice.is_empty() {
-                if let Some(i) = memchr(b'\n', slice) {
-                    if i > 0 && &slice[i - 1] == &b'\r'
-                        && &slice[i] == &b'\n'
-                    {
-                        line = &slice[0..i - 1];
-                        slice = &slice[i + 1..slice.len()];
-                    } else if &slice[i] == &b'\n' {
-                        line = &slice[0..i];
-                        slice = &slice[i + 1..slice.len()];
-                    }
-                } else {
-                    line = slice;
-                    slice = &[];
-                }
-                lines += 1;
-                black_box(lines);
-            }
-            assert_eq!(lines, 172);
-            black_box(line);
-        });
+    fn lines(shortest: usize, longest: usize) -> Spec {
+        Spec { size: SIZE, line_len: (shortest, longest), ..Spec::default() }
     }
 
-    #[bench]
-    fn memchr_unchecked(b: &mut Bencher) {
-        b.iter(|| {
-            unsafe {
-                let mut slice = EXAMPLE_BIG.as_bytes();
-                let mut line = &[][..];
-                let mut lines = 0;
-                while !slice.is_empty() {
-                    if let Some(i) = memchr(b'\n', slice) {
-                        if i > 0 && slice.get_unchecked(i - 1) == &b'\r'
-                            && slice.get_unchecked(i) == &b'\n'
-                        {
-                            line = slice.get_unchecked(0..i - 1);
-                            slice = slice.get_unchecked(i + 1..slice.len());
-                        } else if slice.get_unchecked(i) == &b'\n' {
-                            line = slice.get_unchecked(0..i);
-                            slice = slice.get_unchecked(i + 1..slice.len());
-                        }
-                    } else {
-                        line = slice;
-                        slice = &[];
-                    }
-                    lines += 1;
-                    black_box(line);
-                }
-                assert_eq!(lines, 172);
-                black_box(lines);
-            }
-        });
-    }
-
-    #[bench]
-    fn memchr_iter_unchecked(b: &mut Bencher) {
-        b.iter(|| {
-            unsafe {
-                let mut lines = 0;
-                for line in fast_lines(EXAMPLE_BIG) {
-                    lines += 1;
-                    black_box(line);
-                }
-                assert_eq!(lines, 172);
-                black_box(lines);
-            }
-        });
-    }
-    
-    #[bench]
-    fn memchr_core_unchecked(b: &mut Bencher) {
-        b.iter(|| {
-            unsafe {
-                let mut slice = EXAMPLE_BIG.as_bytes();
-                let mut line = &[][..];
-                let mut lines = 0;
-                while !slice.is_empty() {
-                    if let Some(i) = ::core::slice::memchr::memchr(b'\n', slice) {
-                        if i > 0 && slice.get_unchecked(i - 1) == &b'\r'
-                            && slice.get_unchecked(i) == &b'\n'
-                        {
-                            line = slice.get_unchecked(0..i - 1);
-                            slice = slice.get_unchecked(i + 1..slice.len());
-                        } else if slice.get_unchecked(i) == &b'\n' {
-                            line = slice.get_unchecked(0..i);
-                            slice = slice.get_unchecked(i + 1..slice.len());
-                        }
-                    } else {
-                        line = slice;
-                        slice = &[];
-                    }
-                    lines += 1;
-                    black_box(line);
-                }
-                assert_eq!(lines, 172);
-                black_box(lines);
-            }
-        });
-    }
-    
+    sweep!(split_lines_length, split_lines, {
+        short: lines(1, 8),
+        prose: lines(40, 80),
+        long: lines(500, 1000),
+    });
 }
@@ -0,0 +1,182 @@
+// Runs the benchmark matrix and saves the results, or compares two saved
+// runs and fails if the second is slower.
+//
+//     searchtest-bench run [--out FILE] [--csv] [--ladder] [--perf] [--filter TEXT]
+//                          [--warmup-ms N] [--measure-ms N] [--samples N]
+//     searchtest-bench compare OLD NEW [--threshold PERCENT]
+//
+// Results are JSON unless `--csv` is given or the output file ends in `.csv`,
+// and are read back by the same rule. A summary of each result goes to
+// stderr as it's measured. `--perf` adds the counts of the CPU's performance
+// counters, on Linux.
+
+extern crate searchtest;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+use std::time::Duration;
+
+use searchtest::{harness, ladder, matrix, perf, results};
+use searchtest::results::Record;
+
+const USAGE: &str = "usage:
+    searchtest-bench run [--out FILE] [--csv] [--ladder] [--perf] [--filter TEXT]
+                         [--warmup-ms N] [--measure-ms N] [--samples N]
+    searchtest-bench compare OLD NEW [--threshold PERCENT]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|s| &s[..]) {
+        Some("run") => run(&args[1..]),
+        Some("compare") => compare(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("searchtest-bench: {}", e);
+            process::exit(2);
+        }
+    }
+}
+
+// Options and positional arguments, with `--flag value` pairs for the flags
+// in `with_value`
+struct Args {
+    options: Vec<(String, Option<String>)>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    fn parse(args: &[String], with_value: &[&str], without: &[&str]) -> Result<Args, String> {
+        let mut parsed = Args { options: Vec::new(), positional: Vec::new() };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if with_value.contains(&&arg[..]) {
+                let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+                parsed.options.push((arg.clone(), Some(value.clone())));
+            } else if without.contains(&&arg[..]) {
+                parsed.options.push((arg.clone(), None));
+            } else if arg.starts_with("--") {
+                return Err(format!("unknown option {}\n{}", arg, USAGE));
+            } else {
+                parsed.positional.push(arg.clone());
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.options.iter().rev().find(|(n, _)| n == name).and_then(|(_, v)| v.as_deref())
+    }
+
+    fn number(&self, name: &str, default: f64) -> Result<f64, String> {
+        match self.value(name) {
+            Some(v) => v.parse().map_err(|_| format!("{} takes a number, not '{}'", name, v)),
+            None => Ok(default),
+        }
+    }
+}
+
+fn is_csv(path: &str) -> bool {
+    path.ends_with(".csv")
+}
+
+fn run(args: &[String]) -> Result<bool, String> {
+    let with_value = ["--out", "--filter", "--warmup-ms", "--measure-ms", "--samples"];
+    let args = Args::parse(args, &with_value, &["--csv", "--ladder", "--perf"])?;
+    if !args.positional.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let default = harness::Config::default();
+    let ms = |name, default: Duration| -> Result<Duration, String> {
+        Ok(Duration::from_millis(args.number(name, harness::nanos(default) / 1e6)? as u64))
+    };
+    let config = harness::Config {
+        warmup: ms("--warmup-ms", default.warmup)?,
+        measurement: ms("--measure-ms", default.measurement)?,
+        samples: args.number("--samples", default.samples as f64)? as usize,
+        ..default.clone()
+    };
+    let sizes = if args.flag("--ladder") { Some(ladder::SIZES) } else { None };
+    let filter = args.value("--filter").unwrap_or("");
+
+    let mut counters = if args.flag("--perf") {
+        let counters = perf::Counters::open().ok_or("can't open any performance counters")?;
+        if counters.software_only() {
+            eprintln!("warning: no hardware performance counters, counting only the task clock");
+        }
+        Some(counters)
+    } else {
+        None
+    };
+
+    let records = results::collect(&matrix::manifest(), sizes, filter, &config, counters.as_mut(), |record, stats| {
+        let counts: String = record.counters.iter().map(|&(ref name, n)| format!(", {} {:.1}", name, n)).collect();
+        eprintln!("{}::{} {} bytes: {:.1} ns [{:.1}, {:.1}], {:.0}% of speed of light, {} outliers of {}{}",
+                  record.group, record.strategy, record.size, stats.mean, stats.ci.0, stats.ci.1,
+                  record.speed_of_light, stats.outliers.total(), stats.samples.len(), counts);
+    })?;
+
+    let out = args.value("--out");
+    let csv = args.flag("--csv") || out.is_some_and(is_csv);
+    let write = |w: &mut dyn Write| if csv {
+        results::write_csv(&records, w)
+    } else {
+        results::write_json(&records, w)
+    };
+    let written = match out {
+        Some(path) => File::create(path).and_then(|mut f| write(&mut f)),
+        None => write(&mut io::stdout()),
+    };
+    written.map_err(|e| format!("writing results: {}", e))?;
+    Ok(true)
+}
+
+fn load(path: &str) -> Result<Vec<Record>, String> {
+    let mut text = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut text))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let records = if is_csv(path) { results::parse_csv(&text) } else { results::parse_json(&text) };
+    records.map_err(|e| format!("{}: {}", path, e))
+}
+
+fn compare(args: &[String]) -> Result<bool, String> {
+    let args = Args::parse(args, &["--threshold"], &[])?;
+    if args.positional.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+    let threshold = args.number("--threshold", 5.0)? / 100.0;
+    let old = load(&args.positional[0])?;
+    let new = load(&args.positional[1])?;
+
+    let changes = results::compare(&old, &new, threshold);
+    if changes.is_empty() {
+        return Err("the two runs have no results in common".to_string());
+    }
+    if old[0].cpu != new[0].cpu {
+        println!("warning: comparing runs on different CPUs ({} and {})", old[0].cpu, new[0].cpu);
+    }
+
+    println!("{:<36} {:<24} {:>10} {:>14} {:>14} {:>8}", "group", "strategy", "bytes", "old ns", "new ns", "change");
+    for change in &changes {
+        println!("{:<36} {:<24} {:>10} {:>14.1} {:>14.1} {:>+7.1}%{}",
+                 change.new.group, change.new.strategy, change.new.size,
+                 change.old.ns_per_iter, change.new.ns_per_iter, (change.ratio - 1.0) * 100.0,
+                 if change.regression { "  REGRESSION" } else { "" });
+    }
+
+    let regressions = changes.iter().filter(|c| c.regression).count();
+    if regressions > 0 {
+        println!("{} of {} results are more than {}% slower", regressions, changes.len(), threshold * 100.0);
+    }
+    Ok(regressions == 0)
+}
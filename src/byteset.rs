@@ -0,0 +1,260 @@
+// The strategies compared by the `find_set_of_bytes_*` benches, for finding
+// the first byte that is in a set.
+
+use jetscii::{AsciiChars, Bytes};
+use memchr::{memchr, memchr2, memchr3};
+
+use autotune;
+use matrix::lookup;
+#[cfg(simd)]
+use overread;
+#[cfg(simd)]
+use unchecked;
+
+/// A searcher built for one set of bytes.
+pub trait Searcher {
+    /// The offset of the first byte of `haystack` that is in the set.
+    fn find(&self, haystack: &str) -> Option<usize>;
+}
+
+/// Builds a searcher for a set of bytes, or returns `None` if the strategy
+/// can't search for that set.
+pub type Builder = fn(&[u8]) -> Option<Box<dyn Searcher>>;
+
+pub static STRATEGIES: &[(&str, Builder)] = &[
+    ("jetscii_bytes", build_jetscii_bytes),
+    ("jetscii_ascii", build_jetscii_ascii),
+    ("open_table", build_open_table),
+    ("position_table", build_position_table),
+    ("multi_memchr", build_multi_memchr),
+    ("jump_table", build_jump_table),
+    ("sse2_cmpeq", build_sse2_cmpeq),
+];
+
+/// Builds a searcher for `set` with the strategy `autotune` picked, or with
+/// `jetscii_bytes` if nothing has been tuned, and with `position_table` if
+/// that strategy can't search for the set.
+pub fn build(set: &[u8]) -> Box<dyn Searcher> {
+    let strategy = autotune::byte_set_strategy().unwrap_or("jetscii_bytes");
+    lookup(STRATEGIES, strategy).and_then(|build| build(set))
+        .unwrap_or_else(|| Box::new(PositionTable(table(set))))
+}
+
+/// The bytes that are special somewhere in Markdown.
+pub static MARKDOWN_SPECIAL: &[u8] = b"#_*=-~|[\\>^`&/:@";
+
+pub fn table(set: &[u8]) -> [bool; 256] {
+    let mut table = [false; 256];
+    for byte in set {
+        table[*byte as usize] = true;
+    }
+    table
+}
+
+/// A set of bytes, kept as a table.
+#[derive(Clone, Copy)]
+pub struct ByteSet([bool; 256]);
+
+impl ByteSet {
+    pub fn new(set: &[u8]) -> ByteSet {
+        ByteSet(table(set))
+    }
+
+    pub fn contains(&self, byte: u8) -> bool {
+        self.0[byte as usize]
+    }
+
+    /// The bytes in the set, in order.
+    pub fn bytes(&self) -> Vec<u8> {
+        (0..=255u8).filter(|&b| self.contains(b)).collect()
+    }
+}
+
+// jetscii takes up to 16 bytes in a fixed array, with a fallback for CPUs
+// without SSE 4.2
+fn jetscii_array(set: &[u8]) -> Option<([u8; 16], i32)> {
+    if set.len() > 16 {
+        return None;
+    }
+    let mut array = [0; 16];
+    array[..set.len()].copy_from_slice(set);
+    Some((array, set.len() as i32))
+}
+
+struct JetsciiBytes<F: Fn(u8) -> bool>(Bytes<F>);
+
+impl<F: Fn(u8) -> bool> Searcher for JetsciiBytes<F> {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        self.0.find(haystack.as_bytes())
+    }
+}
+
+fn build_jetscii_bytes(set: &[u8]) -> Option<Box<dyn Searcher>> {
+    let (array, len) = jetscii_array(set)?;
+    let table = table(set);
+    Some(Box::new(JetsciiBytes(Bytes::new(array, len, move |b| table[b as usize]))))
+}
+
+struct JetsciiAscii<F: Fn(u8) -> bool>(AsciiChars<F>);
+
+impl<F: Fn(u8) -> bool> Searcher for JetsciiAscii<F> {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        self.0.find(haystack)
+    }
+}
+
+fn build_jetscii_ascii(set: &[u8]) -> Option<Box<dyn Searcher>> {
+    if !set.is_ascii() {
+        return None;
+    }
+    let (array, len) = jetscii_array(set)?;
+    let table = table(set);
+    Some(Box::new(JetsciiAscii(AsciiChars::new(array, len, move |b| table[b as usize]))))
+}
+
+struct OpenTable([bool; 256]);
+
+impl Searcher for OpenTable {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        for (i, byte) in haystack.as_bytes().iter().enumerate() {
+            if self.0[*byte as usize] {
+                return Some(i);
+            }
+        }
+        None
+    }
+}
+
+fn build_open_table(set: &[u8]) -> Option<Box<dyn Searcher>> {
+    Some(Box::new(OpenTable(table(set))))
+}
+
+struct PositionTable([bool; 256]);
+
+impl Searcher for PositionTable {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        haystack.as_bytes().iter().position(|byte| self.0[*byte as usize])
+    }
+}
+
+fn build_position_table(set: &[u8]) -> Option<Box<dyn Searcher>> {
+    Some(Box::new(PositionTable(table(set))))
+}
+
+struct MultiMemchr(Vec<u8>);
+
+impl Searcher for MultiMemchr {
+    // Takes the first match of the first chunk of three bytes that has one,
+    // which is only the first match overall if the chunks' matches don't
+    // interleave. The fixtures are chosen so they don't.
+    fn find(&self, haystack: &str) -> Option<usize> {
+        let haystack = haystack.as_bytes();
+        for chunk in self.0.chunks(3) {
+            let r = match *chunk {
+                [a, b, c] => memchr3(a, b, c, haystack),
+                [a, b] => memchr2(a, b, haystack),
+                [a] => memchr(a, haystack),
+                _ => unreachable!(),
+            };
+            if r.is_some() {
+                return r;
+            }
+        }
+        None
+    }
+}
+
+fn build_multi_memchr(set: &[u8]) -> Option<Box<dyn Searcher>> {
+    Some(Box::new(MultiMemchr(set.to_vec())))
+}
+
+struct JumpTable;
+
+impl Searcher for JumpTable {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        for (i, ch) in haystack.as_bytes().iter().enumerate() {
+            match *ch {
+                b'#' |  b'_' |  b'*' |  b'=' |  b'-' |  b'~' |  b'|' |  b'[' |
+                b'\\' | b'>' |  b'^' |  b'`' |  b'&' |  b'/' |  b':' |  b'@' => {
+                    return Some(i);
+                }
+                _ => ()
+            }
+        }
+        None
+    }
+}
+
+// The set is compiled into the match, so it only does the Markdown bytes
+fn build_jump_table(set: &[u8]) -> Option<Box<dyn Searcher>> {
+    let mut set = set.to_vec();
+    let mut markdown = MARKDOWN_SPECIAL.to_vec();
+    set.sort();
+    markdown.sort();
+    if set == markdown {
+        Some(Box::new(JumpTable))
+    } else {
+        None
+    }
+}
+
+// Compares 16 bytes of the haystack at a time with each byte of the set, and
+// finishes with a load past the end of the haystack where that's safe
+#[cfg(simd)]
+struct Sse2Cmpeq {
+    vectors: Vec<::arch::__m128i>,
+    table: [bool; 256],
+}
+
+#[cfg(simd)]
+impl Searcher for Sse2Cmpeq {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        unsafe { self.find_sse2(haystack.as_bytes()) }
+    }
+}
+
+#[cfg(simd)]
+impl Sse2Cmpeq {
+    #[target_feature(enable = "sse2")]
+    unsafe fn find_sse2(&self, haystack: &[u8]) -> Option<usize> {
+        use arch::*;
+
+        let matches = |ptr: *const u8| {
+            let vec = _mm_loadu_si128(ptr as *const __m128i);
+            let eq = self.vectors.iter().fold(_mm_setzero_si128(), |acc, &v| _mm_or_si128(acc, _mm_cmpeq_epi8(vec, v)));
+            _mm_movemask_epi8(eq) as u32
+        };
+
+        let mut i = 0;
+        while haystack.len() - i >= 16 {
+            let mask = matches(unchecked::get(haystack, i..i + 16).as_ptr());
+            if mask != 0 {
+                return Some(i + mask.trailing_zeros() as usize);
+            }
+            i += 16;
+        }
+
+        if i < haystack.len() && overread::can_load(unchecked::get(haystack, i..).as_ptr(), 16) {
+            let mask = matches(unchecked::get(haystack, i..).as_ptr()) & overread::lanes(haystack.len() - i);
+            return if mask != 0 { Some(i + mask.trailing_zeros() as usize) } else { None };
+        }
+        unchecked::get(haystack, i..).iter().position(|&b| self.table[b as usize]).map(|j| i + j)
+    }
+}
+
+// A vector per byte of the set, so only small sets
+#[cfg(simd)]
+fn build_sse2_cmpeq(set: &[u8]) -> Option<Box<dyn Searcher>> {
+    use arch::_mm_set1_epi8;
+
+    if set.is_empty() || set.len() > 16 || !is_x86_feature_detected!("sse2") {
+        return None;
+    }
+    let vectors = set.iter().map(|&b| unsafe { _mm_set1_epi8(b as i8) }).collect();
+    Some(Box::new(Sse2Cmpeq { vectors: vectors, table: table(set) }))
+}
+
+#[cfg(not(simd))]
+fn build_sse2_cmpeq(_: &[u8]) -> Option<Box<dyn Searcher>> {
+    None
+}
@@ -0,0 +1,115 @@
+// Generated benchmark text, for varying the things the hand-made fixtures
+// hold fixed: size, where the match is, how many near misses come before it,
+// and what the text around it looks like.
+
+use byteset::ByteSet;
+use rng::Rng;
+
+#[derive(Clone)]
+pub struct Spec {
+    /// Length of the text in bytes.
+    pub size: usize,
+    /// What is being searched for. Must be ASCII.
+    pub needle: String,
+    /// Where the only occurrence of `needle` goes, if anywhere. Clamped so
+    /// the needle fits.
+    pub match_at: Option<usize>,
+    /// Chance, at each position, of a near miss: all of `needle` but its
+    /// last byte.
+    pub near_miss_density: f64,
+    /// Bytes to sprinkle through the text. Must be ASCII.
+    pub set: ByteSet,
+    /// Chance of each filler byte coming from `set`.
+    pub set_density: f64,
+    /// Chance of each filler char being non-ASCII.
+    pub non_ascii_density: f64,
+    /// Shortest and longest line, newline included.
+    pub line_len: (usize, usize),
+}
+
+impl Default for Spec {
+    fn default() -> Spec {
+        Spec {
+            size: 4096,
+            needle: "www.".to_string(),
+            match_at: None,
+            near_miss_density: 0.0,
+            set: ByteSet::new(&[]),
+            set_density: 0.0,
+            non_ascii_density: 0.0,
+            line_len: (40, 80),
+        }
+    }
+}
+
+static FILLER: &[u8] = b"abcdefghijklmnopqrstuvwxyz      ";
+static NON_ASCII: &[char] = &['é', 'я', 'ї', '€', '→', '😀'];
+
+/// Generates text to `spec`. The same spec and seed give the same text.
+pub fn generate(spec: &Spec, seed: u64) -> String {
+    assert!(spec.needle.is_ascii() && !spec.needle.is_empty(), "needle must be non-empty ASCII");
+    assert!(spec.set.bytes().is_ascii(), "set must be ASCII");
+    assert!(spec.line_len.0 > 0 && spec.line_len.0 <= spec.line_len.1);
+
+    let mut rng = Rng::new(seed);
+    let needle = spec.needle.as_bytes();
+    let set = spec.set.bytes();
+    let match_at = spec.match_at.map(|at| at.min(spec.size.saturating_sub(needle.len())));
+
+    let mut text = String::with_capacity(spec.size);
+    let mut line_end = rng.between(spec.line_len.0, spec.line_len.1);
+
+    while text.len() < spec.size {
+        let len = text.len();
+        // Nothing may straddle the match or run off the end
+        let room = match match_at {
+            Some(at) if len < at => at - len,
+            _ => spec.size - len,
+        };
+
+        if Some(len) == match_at && needle.len() <= room {
+            text.push_str(&spec.needle);
+        } else if len + 1 >= line_end {
+            text.push('\n');
+            line_end = len + rng.between(spec.line_len.0, spec.line_len.1);
+        } else if needle.len() > 1 && needle.len() <= room && rng.chance(spec.near_miss_density) {
+            text.push_str(&spec.needle[..needle.len() - 1]);
+        } else if !set.is_empty() && rng.chance(spec.set_density) {
+            text.push(*rng.pick(&set) as char);
+        } else if rng.chance(spec.non_ascii_density) {
+            let c = *rng.pick(NON_ASCII);
+            text.push(if c.len_utf8() <= room { c } else { '?' });
+        } else {
+            text.push(*rng.pick(FILLER) as char);
+        }
+    }
+
+    remove_stray_matches(&mut text, needle, match_at);
+    text
+}
+
+// Breaks every occurrence of the needle other than the planted one, by
+// replacing its first byte, or its last if it comes after the planted one,
+// so an overlap never breaks that. Everything touched is ASCII, so the text
+// stays valid UTF-8.
+fn remove_stray_matches(text: &mut String, needle: &[u8], match_at: Option<usize>) {
+    let mut bytes = ::std::mem::take(text).into_bytes();
+
+    let mut from = 0;
+    while let Some(i) = find(&bytes[from..], needle).map(|i| from + i) {
+        if Some(i) == match_at {
+            from = i + 1;
+        } else {
+            let k = if match_at.is_some_and(|at| at < i) { needle.len() - 1 } else { 0 };
+            bytes[i + k] = if needle[k] == b'x' { b'y' } else { b'x' };
+            // The new byte may complete an earlier overlapping occurrence
+            from = (i + k + 1).saturating_sub(needle.len());
+        }
+    }
+
+    *text = String::from_utf8(bytes).expect("only ASCII bytes were replaced");
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
@@ -0,0 +1,19 @@
+// The example documents the benchmarks search, by file name.
+
+pub static FIXTURES: &[(&str, &str)] = &[
+    ("comrak-readme.md", include_str!("comrak-readme.md")),
+    ("late-unicode.md", include_str!("late-unicode.md")),
+    ("lipsum-at.md", include_str!("lipsum-at.md")),
+    ("lipsum-emph.md", include_str!("lipsum-emph.md")),
+    ("lipsum-linebreaks.md", include_str!("lipsum-linebreaks.md")),
+    ("lipsum.md", include_str!("lipsum.md")),
+    ("simple.md", include_str!("simple.md")),
+    ("unicode.md", include_str!("unicode.md")),
+    ("www.md", include_str!("www.md")),
+    ("www2.md", include_str!("www2.md")),
+    ("www3.md", include_str!("www3.md")),
+];
+
+pub fn fixture(name: &str) -> Option<&'static str> {
+    FIXTURES.iter().find(|&&(n, _)| n == name).map(|&(_, text)| text)
+}
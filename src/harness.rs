@@ -0,0 +1,164 @@
+// A benchmark harness that doesn't need `test::Bencher`, and so runs on
+// stable. It warms up, sizes its batches to the time it's given, and reports
+// the spread of the samples as well as their middle.
+
+use std::time::{Duration, Instant};
+
+use rng::Rng;
+
+pub use std::hint::black_box;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// How long to run before measuring anything.
+    pub warmup: Duration,
+    /// Roughly how long all the samples take together.
+    pub measurement: Duration,
+    pub samples: usize,
+    /// The confidence level of `Stats::ci`, such as 0.95.
+    pub confidence: f64,
+    /// How many times the samples are resampled to find `Stats::ci`.
+    pub resamples: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            warmup: Duration::from_millis(100),
+            measurement: Duration::from_millis(500),
+            samples: 50,
+            confidence: 0.95,
+            resamples: 1000,
+        }
+    }
+}
+
+/// How many samples fall outside Tukey's fences: mild ones are more than 1.5
+/// interquartile ranges beyond the quartiles, severe ones more than 3.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Outliers {
+    pub low_severe: usize,
+    pub low_mild: usize,
+    pub high_mild: usize,
+    pub high_severe: usize,
+}
+
+impl Outliers {
+    pub fn total(&self) -> usize {
+        self.low_severe + self.low_mild + self.high_mild + self.high_severe
+    }
+}
+
+/// The samples of one benchmark, in nanoseconds per iteration.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    pub samples: Vec<f64>,
+    pub iters_per_sample: u64,
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,
+    /// The bootstrap confidence interval of the mean.
+    pub ci: (f64, f64),
+    pub outliers: Outliers,
+}
+
+/// Times `f`, to `config`.
+pub fn measure<F: FnMut() -> R, R>(config: &Config, mut f: F) -> Stats {
+    // Warm up in doubling batches, which also estimates the time per call
+    let mut iters = 0u64;
+    let mut batch = 1u64;
+    let start = Instant::now();
+    loop {
+        for _ in 0..batch {
+            black_box(f());
+        }
+        iters += batch;
+        if start.elapsed() >= config.warmup {
+            break;
+        }
+        batch *= 2;
+    }
+    // At least a nanosecond, as a run too short for the clock to see would
+    // otherwise size the samples at `u64::MAX` iterations
+    let per_iter = (nanos(start.elapsed()) / iters as f64).max(1.0);
+
+    let samples = config.samples.max(2);
+    let per_sample = nanos(config.measurement) / samples as f64;
+    let iters_per_sample = ((per_sample / per_iter) as u64).max(1);
+
+    let samples: Vec<f64> = (0..samples).map(|_| {
+        let start = Instant::now();
+        for _ in 0..iters_per_sample {
+            black_box(f());
+        }
+        nanos(start.elapsed()) / iters_per_sample as f64
+    }).collect();
+
+    let mut sorted = samples.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mean = mean(&samples);
+
+    Stats {
+        iters_per_sample: iters_per_sample,
+        mean: mean,
+        median: percentile(&sorted, 0.5),
+        stddev: stddev(&samples, mean),
+        ci: bootstrap(&samples, config.confidence, config.resamples),
+        outliers: outliers(&sorted),
+        samples: samples,
+    }
+}
+
+pub fn nanos(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1e9 + d.subsec_nanos() as f64
+}
+
+fn mean(xs: &[f64]) -> f64 {
+    xs.iter().sum::<f64>() / xs.len() as f64
+}
+
+fn stddev(xs: &[f64], mean: f64) -> f64 {
+    let var = xs.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (xs.len() - 1) as f64;
+    var.sqrt()
+}
+
+/// Interpolates between the two nearest of the sorted samples.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p * (sorted.len() - 1) as f64;
+    let below = rank.floor() as usize;
+    let above = rank.ceil() as usize;
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64)
+}
+
+/// Counts the outliers among the sorted samples.
+pub fn outliers(sorted: &[f64]) -> Outliers {
+    let q1 = percentile(sorted, 0.25);
+    let q3 = percentile(sorted, 0.75);
+    let iqr = q3 - q1;
+    let mut outliers = Outliers::default();
+    for &x in sorted {
+        if x < q1 - 3.0 * iqr {
+            outliers.low_severe += 1;
+        } else if x < q1 - 1.5 * iqr {
+            outliers.low_mild += 1;
+        } else if x > q3 + 3.0 * iqr {
+            outliers.high_severe += 1;
+        } else if x > q3 + 1.5 * iqr {
+            outliers.high_mild += 1;
+        }
+    }
+    outliers
+}
+
+/// The percentile interval of the means of resamples. The seed is fixed so
+/// the same samples always give the same interval.
+pub fn bootstrap(samples: &[f64], confidence: f64, resamples: usize) -> (f64, f64) {
+    let mut rng = Rng::new(0);
+    let mut means: Vec<f64> = (0..resamples.max(1)).map(|_| {
+        let total: f64 = (0..samples.len()).map(|_| *rng.pick(samples)).sum();
+        total / samples.len() as f64
+    }).collect();
+    means.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let tail = (1.0 - confidence) / 2.0;
+    (percentile(&means, tail), percentile(&means, 1.0 - tail))
+}
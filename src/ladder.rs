@@ -0,0 +1,128 @@
+// Runs the manifest's strategies over generated text of sizes from nothing
+// to 64 MiB, to show setup costs on tiny inputs and bandwidth limits on huge
+// ones.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use baseline;
+use corpus::{self, Spec};
+use harness::{black_box, nanos};
+use manifest::{Group, Manifest, Outcome, Search};
+use matrix;
+
+/// 0, then powers of 4 up to 64 MiB.
+pub static SIZES: &[usize] = &[
+    0, 1, 4, 16, 64, 256,
+    1 << 10, 4 << 10, 16 << 10, 64 << 10, 256 << 10,
+    1 << 20, 4 << 20, 16 << 20, 64 << 20,
+];
+
+/// Text of `size` bytes for `group`'s search, with whatever it looks for as
+/// close to the end as fits, so every strategy has to get through all of it.
+pub fn text(group: &Group, size: usize) -> String {
+    let mut spec = Spec { size: size, ..Spec::default() };
+    match group.search {
+        Search::ByteSet(ref set) => {
+            if let Some(&byte) = set.iter().find(|b| b.is_ascii()) {
+                spec.needle = (byte as char).to_string();
+                spec.match_at = Some(size);
+            }
+        }
+        Search::Substring(ref needle) if needle.is_ascii() => {
+            spec.needle = needle.clone();
+            spec.match_at = Some(size);
+        }
+        _ => {}
+    }
+
+    let mut text = corpus::generate(&spec, 0);
+    // The generated text is ASCII, so any cut is a char boundary
+    if group.expect == Outcome::Bool(false) && size >= 2 {
+        text.truncate(size - 2);
+        text.push('é');
+    }
+    text
+}
+
+/// How long a number of runs over an input took.
+#[derive(Clone, Copy, Debug)]
+pub struct Timing {
+    pub bytes: usize,
+    pub iters: u64,
+    pub elapsed: Duration,
+}
+
+impl Timing {
+    pub fn ns_per_op(&self) -> f64 {
+        nanos(self.elapsed) / self.iters as f64
+    }
+
+    /// Bytes per nanosecond is GB per second.
+    pub fn gb_per_s(&self) -> f64 {
+        self.bytes as f64 / self.ns_per_op()
+    }
+}
+
+/// Repeats `run`, doubling the number of runs until they take at least
+/// `budget`.
+pub fn time<F: FnMut() -> R, R>(mut run: F, bytes: usize, budget: Duration) -> Timing {
+    let mut iters = 1;
+    loop {
+        let start = Instant::now();
+        for _ in 0..iters {
+            black_box(run());
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= budget || iters >= 1 << 32 {
+            return Timing { bytes: bytes, iters: iters, elapsed: elapsed };
+        }
+        iters *= 2;
+    }
+}
+
+/// Times the baselines and then every strategy of every group at each size,
+/// one row per run, with each strategy's speed as a percentage of the speed
+/// of light at that size. A strategy that gets the wrong answer says so
+/// instead of being timed.
+pub fn report<W: Write>(manifest: &Manifest, sizes: &[usize], budget: Duration, mut out: W)
+                        -> io::Result<()> {
+    writeln!(out, "{:<36} {:<24} {:>10} {:>14} {:>8} {:>7}", "group", "strategy", "bytes", "ns/op", "GB/s", "%SoL")?;
+
+    let mut speed_of_light = Vec::new();
+    for &size in sizes {
+        let bytes = baseline::buffer(size);
+        for name in baseline::BASELINES {
+            let timing = time(baseline::prepare(name, &bytes).unwrap(), size, budget);
+            writeln!(out, "{:<36} {:<24} {:>10} {:>14.1} {:>8.2}",
+                     "baseline", name, size, timing.ns_per_op(), timing.gb_per_s())?;
+            if *name == baseline::SPEED_OF_LIGHT {
+                speed_of_light.push(timing.ns_per_op());
+            }
+        }
+    }
+
+    for group in &manifest.groups {
+        for (&size, &sol) in sizes.iter().zip(&speed_of_light) {
+            let text = text(group, size);
+            let expect = matrix::reference(&group.search, &text);
+            for strategy in &group.strategies {
+                let mut run = match matrix::prepare_text(group, strategy, &text) {
+                    Ok(Some(run)) => run,
+                    Ok(None) => continue,
+                    Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
+                };
+                write!(out, "{:<36} {:<24} {:>10} ", group.name, strategy, size)?;
+                let outcome = run();
+                if !outcome.satisfies(expect) {
+                    writeln!(out, "wrong: {:?} instead of {:?}", outcome, expect)?;
+                    continue;
+                }
+                let timing = time(&mut run, size, budget);
+                writeln!(out, "{:>14.1} {:>8.2} {:>6.0}%", timing.ns_per_op(), timing.gb_per_s(),
+                         baseline::percent_of(sol, timing.ns_per_op()))?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,166 @@
+// Times single calls instead of the throughput of a hot loop: with the input
+// flushed from the cache before each call, and on inputs of a few bytes,
+// where the fixed cost of a call is all there is.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use harness::{black_box, nanos};
+use ladder;
+use manifest::{Group, Manifest};
+use matrix::{self, Runner};
+
+/// The groups the latency reports run.
+pub static GROUPS: &[&str] = &[
+    "is_ascii", "is_not_ascii", "find_set_of_bytes_early", "find_set_of_bytes_late", "split_lines",
+];
+
+/// Lengths at and either side of the vector widths the strategies use.
+pub static TINY_SIZES: &[usize] = &[1, 2, 3, 4, 7, 8, 9, 15, 16, 17, 31, 32, 33, 63, 64];
+
+/// Evicts `bytes` from every level of the cache.
+pub fn flush(bytes: &[u8]) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    unsafe {
+        use arch::{_mm_clflush, _mm_mfence};
+
+        for line in bytes.chunks(64) {
+            _mm_clflush(line.as_ptr());
+        }
+        // Make sure the flushes are done before whatever comes next
+        _mm_mfence();
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    let _ = bytes;
+}
+
+/// Percentiles of the time one call takes, in nanoseconds, less the cost of
+/// reading the clock.
+#[derive(Clone, Copy, Debug)]
+pub struct Latency {
+    pub min: f64,
+    pub median: f64,
+    pub p99: f64,
+}
+
+impl Latency {
+    fn from_samples(mut samples: Vec<f64>) -> Latency {
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let at = |p: f64| samples[((samples.len() - 1) as f64 * p) as usize];
+        Latency { min: at(0.0), median: at(0.5), p99: at(0.99) }
+    }
+}
+
+// The time stamp counter where there is one, since `Instant` can be too
+// coarse for a call that takes a few nanoseconds
+struct Clock {
+    start: Instant,
+    ticks_per_ns: f64,
+}
+
+impl Clock {
+    fn new() -> Clock {
+        let mut clock = Clock { start: Instant::now(), ticks_per_ns: 1.0 };
+        let ticks = clock.ticks();
+        while clock.start.elapsed() < Duration::from_millis(10) {}
+        clock.ticks_per_ns = (clock.ticks() - ticks) as f64 / nanos(clock.start.elapsed());
+        clock
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn ticks(&self) -> u64 {
+        use arch::{_mm_lfence, _rdtsc};
+
+        // The fences keep the call being timed from moving across the read
+        unsafe {
+            _mm_lfence();
+            let ticks = _rdtsc();
+            _mm_lfence();
+            ticks
+        }
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    fn ticks(&self) -> u64 {
+        nanos(self.start.elapsed()) as u64
+    }
+
+    fn ns(&self, ticks: u64) -> f64 {
+        ticks as f64 / self.ticks_per_ns
+    }
+}
+
+/// Times `calls` single calls of `run`, calling `before` untimed ahead of
+/// each.
+pub fn time_calls<B: FnMut(), F: FnMut() -> R, R>(calls: usize, mut before: B, mut run: F) -> Latency {
+    let clock = Clock::new();
+    // The median time to read the clock twice with nothing in between
+    let overhead = Latency::from_samples((0..1000).map(|_| {
+        let start = clock.ticks();
+        clock.ns(clock.ticks() - start)
+    }).collect()).median;
+
+    let samples = (0..calls.max(1)).map(|_| {
+        before();
+        let start = clock.ticks();
+        black_box(run());
+        (clock.ns(clock.ticks() - start) - overhead).max(0.0)
+    }).collect();
+    Latency::from_samples(samples)
+}
+
+/// Times single calls of every strategy in `GROUPS` over its fixture, warm
+/// and with the fixture flushed from the cache first.
+pub fn report_cold<W: Write>(manifest: &Manifest, calls: usize, mut out: W) -> io::Result<()> {
+    writeln!(out, "{:<24} {:<24} {:>10} {:>10} {:>10} {:>10}",
+             "group", "strategy", "warm", "cold", "cold min", "cold p99")?;
+    for_each_strategy(manifest, |group, strategy| {
+        let fixture = ::fixtures::fixture(&group.fixture).unwrap_or("");
+        let mut run = match prepare(group, strategy, fixture)? {
+            Some(run) => run,
+            None => return Ok(()),
+        };
+        let warm = time_calls(calls, || {}, &mut run);
+        let cold = time_calls(calls, || flush(fixture.as_bytes()), &mut run);
+        writeln!(out, "{:<24} {:<24} {:>10.1} {:>10.1} {:>10.1} {:>10.1}",
+                 group.name, strategy, warm.median, cold.median, cold.min, cold.p99)
+    })
+}
+
+/// Times single calls of every strategy in `GROUPS` over generated inputs of
+/// each of `TINY_SIZES`.
+pub fn report_tiny<W: Write>(manifest: &Manifest, calls: usize, mut out: W) -> io::Result<()> {
+    writeln!(out, "{:<24} {:<24} {:>6} {:>10} {:>10} {:>10}",
+             "group", "strategy", "bytes", "min", "median", "p99")?;
+    for_each_strategy(manifest, |group, strategy| {
+        for &size in TINY_SIZES {
+            let text = ladder::text(group, size);
+            let mut run = match prepare(group, strategy, &text)? {
+                Some(run) => run,
+                None => return Ok(()),
+            };
+            let latency = time_calls(calls, || {}, &mut run);
+            writeln!(out, "{:<24} {:<24} {:>6} {:>10.1} {:>10.1} {:>10.1}",
+                     group.name, strategy, size, latency.min, latency.median, latency.p99)?;
+        }
+        Ok(())
+    })
+}
+
+fn for_each_strategy<F>(manifest: &Manifest, mut f: F) -> io::Result<()>
+    where F: FnMut(&Group, &str) -> io::Result<()>
+{
+    for name in GROUPS {
+        let group = manifest.group(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("no group named {}", name))
+        })?;
+        for strategy in &group.strategies {
+            f(group, strategy)?;
+        }
+    }
+    Ok(())
+}
+
+fn prepare<'a>(group: &'a Group, strategy: &str, text: &'a str) -> io::Result<Option<Runner<'a>>> {
+    matrix::prepare_text(group, strategy, text).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
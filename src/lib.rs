@@ -1,322 +1,474 @@
-#![feature(test)]
-#![feature(stdsimd)]
-#![feature(mmx_target_feature)]
-#![feature(slice_internals)]
+#![cfg_attr(feature = "nightly", feature(slice_internals), allow(internal_features))]
+#![cfg_attr(all(test, feature = "nightly"), feature(test))]
+// The kernels' only safety condition is the CPU feature in their name, and
+// the crate spells out `field: field`
+#![allow(clippy::missing_safety_doc, clippy::redundant_field_names)]
 
+#[cfg(feature = "nightly")]
 extern crate core;
-extern crate stdsimd;
+#[cfg(all(test, feature = "nightly"))]
 extern crate test;
-#[macro_use]
+#[cfg_attr(all(test, feature = "nightly"), macro_use)]
 extern crate jetscii;
-extern crate faster;
+#[cfg(target_os = "linux")]
+extern crate libc;
 extern crate memchr;
 extern crate twoway;
 
+use std::borrow::Cow;
+
+use autotune::Level;
+
+// The intrinsics for whichever of 32 and 64-bit x86 this is built for. The
+// kernels only use the ones the two have in common, so those named
+// `*_x86_64_*` are built for 32-bit x86 as well.
+//
+// To run the tests as 32-bit, with the 32-bit glibc installed (`gcc-multilib`
+// on Debian and Ubuntu):
+//
+//     rustup target add i686-unknown-linux-gnu
+//     cargo test --target i686-unknown-linux-gnu
+//
+// Without it, musl links with the linker rustup ships:
+//
+//     rustup target add i686-unknown-linux-musl
+//     RUSTFLAGS="-C linker=rust-lld -C link-self-contained=yes" \
+//         cargo test --target i686-unknown-linux-musl
+#[cfg(target_arch = "x86")]
+use std::arch::x86 as arch;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64 as arch;
+
+pub use line_index::{ColumnUnit, LineCol, LineIndex};
+
+// First, so the modules after it can use `multiversion!`
+#[macro_use]
+pub mod multiversion;
+
+pub mod alignment;
+pub mod amortise;
+pub mod autotune;
+pub mod baseline;
+pub mod byteset;
+pub mod corpus;
+pub mod fixtures;
+pub mod harness;
+pub mod latency;
+pub mod ladder;
+pub mod line_splitters;
+pub mod manifest;
+pub mod matrix;
+pub mod perf;
+pub mod results;
+pub mod rng;
+pub mod substring;
+
+mod line_index;
+mod newlines;
+#[cfg(simd)]
+mod overread;
+mod unchecked;
+
 #[cfg(test)]
 mod bench;
+#[cfg(test)]
+mod tests;
+
+// The top bit of each byte of a word, for checking eight bytes at a time in
+// a general purpose register where the kernels once used MMX
+#[cfg(simd)]
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
 
 // FIXME: Trying doing this with aligned instructions
 pub fn is_ascii_simd(slice: &[u8]) -> bool {
 
-    return if cfg!(target_arch = "x86_64") &&
-        ((cfg!(target_feature = "avx2") ||
-          cfg!(target_feature = "sse2") ||
-          cfg!(target_feature = "sse") ||
-          cfg!(target_feature = "mmx")) ||
-         (is_x86_feature_detected!("avx2") ||
-          is_x86_feature_detected!("sse2") ||
-          is_x86_feature_detected!("sse") ||
-          is_x86_feature_detected!("mmx")))
+    #[cfg(simd)]
     {
-        unsafe { is_ascii_simd_x86_64(slice) }
-    } else {
-        slice.is_ascii()
-    };
-
-    #[cfg(target_arch = "x86_64")]
-    unsafe fn is_ascii_simd_x86_64(slice: &[u8]) -> bool {
-        use std::arch::x86_64::*;
-        use std::simd::{u8x32, u8x16, u8x8};
-        use std::simd::FromBits;
+        if (cfg!(target_feature = "avx2") ||
+            cfg!(target_feature = "sse2") ||
+            cfg!(target_feature = "sse")) ||
+           (is_x86_feature_detected!("avx2") ||
+            is_x86_feature_detected!("sse2") ||
+            is_x86_feature_detected!("sse"))
+        {
+            return is_ascii_simd_x86_64(slice);
+        }
+    }
 
-        let mut slice = slice;
+    return slice.is_ascii();
 
-        if cfg!(target_feature = "avx2") ||
-            is_x86_feature_detected!("avx2")
-        {
-            while slice.len() >= 32 {
-                let vec = u8x32::load_unaligned(&slice[..32]);
-                let vec: __m256i = __m256i::from_bits(vec);
-                if _mm256_movemask_epi8(vec) != 0 {
-                    return false;
+    // It mixes levels, so it's compiled once per level, for each one's
+    // intrinsics to inline into
+    #[cfg(simd)]
+    multiversion! {
+        fn is_ascii_simd_x86_64(slice: &[u8]) -> bool {
+            unsafe {
+                use std::ptr;
+
+                use arch::*;
+
+                let mut slice = slice;
+
+                if cfg!(target_feature = "avx2") ||
+                    is_x86_feature_detected!("avx2")
+                {
+                    while slice.len() >= 32 {
+                        let vec = _mm256_loadu_si256(unchecked::get(slice, ..32).as_ptr() as *const __m256i);
+                        if _mm256_movemask_epi8(vec) != 0 {
+                            return false;
+                        }
+                        slice = unchecked::get(slice, 32..);
+                    }
+                    debug_assert!(slice.len() < 32);
                 }
-                slice = &slice[32..];
-            }
-            debug_assert!(slice.len() < 32);
-        }
 
-        if cfg!(target_feature = "sse2") ||
-            is_x86_feature_detected!("sse2")
-        {
-            while slice.len() >= 16 {
-                let vec = u8x16::load_unaligned(&slice[..16]);
-                let vec: __m128i = __m128i::from_bits(vec);
-                if _mm_movemask_epi8(vec) != 0 {
-                    return false;
+                if cfg!(target_feature = "sse2") ||
+                    is_x86_feature_detected!("sse2")
+                {
+                    while slice.len() >= 16 {
+                        let vec = _mm_loadu_si128(unchecked::get(slice, ..16).as_ptr() as *const __m128i);
+                        if _mm_movemask_epi8(vec) != 0 {
+                            return false;
+                        }
+                        slice = unchecked::get(slice, 16..);
+                    }
+                    debug_assert!(slice.len() < 16);
                 }
-                slice = &slice[16..];
-            }
-            debug_assert!(slice.len() < 16);
-        }
 
-        if cfg!(target_feature = "sse") ||
-            is_x86_feature_detected!("sse")
-        {
-            while slice.len() >= 8 {
-                let vec = u8x8::load_unaligned(&slice[..8]);
-                let vec: __m64 = __m64::from_bits(vec);
-                if _mm_movemask_pi8(vec) != 0 {
-                    return false;
+                if cfg!(target_feature = "sse") ||
+                    is_x86_feature_detected!("sse")
+                {
+                    while slice.len() >= 8 {
+                        let word = ptr::read_unaligned(unchecked::get(slice, ..8).as_ptr() as *const u64);
+                        if word & HIGH_BITS != 0 {
+                            return false;
+                        }
+                        slice = unchecked::get(slice, 8..);
+                    }
+                    debug_assert!(slice.len() < 8);
                 }
-                slice = &slice[8..];
+
+                slice.is_ascii()
             }
-            debug_assert!(slice.len() < 8);
         }
-
-        slice.is_ascii()
     }
 }
 
 pub fn is_ascii_simd2(slice: &[u8]) -> bool {
 
-    return if cfg!(target_arch = "x86_64") &&
-        ((cfg!(target_feature = "avx2") ||
-          cfg!(target_feature = "sse2") ||
-          cfg!(target_feature = "sse") ||
-          cfg!(target_feature = "mmx")) ||
-         (is_x86_feature_detected!("avx2") ||
-          is_x86_feature_detected!("sse2") ||
-          is_x86_feature_detected!("sse") ||
-          is_x86_feature_detected!("mmx")))
+    #[cfg(simd)]
     {
-        unsafe { is_ascii_simd_x86_64(slice) }
-    } else {
-        slice.is_ascii()
-    };
+        if (cfg!(target_feature = "avx2") ||
+            cfg!(target_feature = "sse2") ||
+            cfg!(target_feature = "sse")) ||
+           (is_x86_feature_detected!("avx2") ||
+            is_x86_feature_detected!("sse2") ||
+            is_x86_feature_detected!("sse"))
+        {
+            return is_ascii_simd_x86_64(slice);
+        }
+    }
+
+    return slice.is_ascii();
+
+    // It mixes levels, so it's compiled once per level, for each one's
+    // intrinsics to inline into
+    #[cfg(simd)]
+    multiversion! {
+        fn is_ascii_simd_x86_64(slice: &[u8]) -> bool {
+            unsafe {
+                use std::ptr;
 
-    #[cfg(target_arch = "x86_64")]
-    unsafe fn is_ascii_simd_x86_64(slice: &[u8]) -> bool {
-        use std::arch::x86_64::*;
-        use std::simd::{u8x32, u8x16, u8x8};
-        use std::simd::FromBits;
+                use arch::*;
 
-        let have_avx2 = cfg!(target_feature = "avx2") ||
-            is_x86_feature_detected!("avx2");
-        let have_sse2 = cfg!(target_feature = "sse2") ||
-            is_x86_feature_detected!("sse2");
-        let have_sse = cfg!(target_feature = "sse") ||
-            is_x86_feature_detected!("sse");
+                let have_avx2 = cfg!(target_feature = "avx2") ||
+                    is_x86_feature_detected!("avx2");
+                let have_sse2 = cfg!(target_feature = "sse2") ||
+                    is_x86_feature_detected!("sse2");
+                let have_sse = cfg!(target_feature = "sse") ||
+                    is_x86_feature_detected!("sse");
 
-        let mut slice = slice;
+                let mut slice = slice;
 
-        let avx2_align = 32;
-        let sse2_align = 16;
-        let sse_align = 8;
+                let avx2_align = 32;
+                let sse2_align = 16;
+                let sse_align = 8;
 
-        let is_aligned = |addr: usize, align: usize| addr & (align - 1) == 0;
+                let is_aligned = |addr: usize, align: usize| addr & (align - 1) == 0;
 
-        let max_align = if have_avx2 { avx2_align }
-        else if have_sse2 { sse2_align }
-        else if have_sse { sse_align }
-        else { 1 };
+                let max_align = if have_avx2 { avx2_align }
+                else if have_sse2 { sse2_align }
+                else if have_sse { sse_align }
+                else { 1 };
         
-        loop {
-            if slice.is_empty() { return true }
-            let addr = slice.get_unchecked(0) as *const _ as usize;
-            if is_aligned(addr, max_align) {
-                break;
-            }
-            if have_sse2 && is_aligned(addr, sse2_align) && slice.len() >= 16 {
-                let vec = u8x16::load_aligned_unchecked(slice.get_unchecked(..16));
-                let vec: __m128i = __m128i::from_bits(vec);
-                if _mm_movemask_epi8(vec) != 0 {
-                    return false;
+                loop {
+                    if slice.is_empty() { return true }
+                    let addr = unchecked::get(slice, 0) as *const _ as usize;
+                    if is_aligned(addr, max_align) {
+                        break;
+                    }
+                    if have_sse2 && is_aligned(addr, sse2_align) && slice.len() >= 16 {
+                        let vec = _mm_load_si128(unchecked::get(slice, ..16).as_ptr() as *const __m128i);
+                        if _mm_movemask_epi8(vec) != 0 {
+                            return false;
+                        }
+                        slice = unchecked::get(slice, 16..);
+                    } else if have_sse && is_aligned(addr, sse_align) && slice.len() >= 8 {
+                        let word = ptr::read(unchecked::get(slice, ..8).as_ptr() as *const u64);
+                        if word & HIGH_BITS != 0 {
+                            return false;
+                        }
+                        slice = unchecked::get(slice, 8..);
+                    } else {
+                        if !unchecked::get(slice, 0).is_ascii() {
+                            return false;
+                        }
+                        slice = unchecked::get(slice, 1..);
+                    }
                 }
-                slice = slice.get_unchecked(16..);
-            } else if have_sse && is_aligned(addr, sse_align) && slice.len() >= 8 {
-                let vec = u8x8::load_aligned_unchecked(slice.get_unchecked(..8));
-                let vec: __m64 = __m64::from_bits(vec);
-                if _mm_movemask_pi8(vec) != 0 {
-                    return false;
-                }
-                slice = slice.get_unchecked(8..);
-            } else {
-                if !slice.get_unchecked(0).is_ascii() {
-                    return false;
-                }
-                slice = slice.get_unchecked(1..);
-            }
-        }
 
-        if have_avx2 {
-            while slice.len() >= 32 {
-                let vec = u8x32::load_aligned_unchecked(slice.get_unchecked(..32));
-                let vec: __m256i = __m256i::from_bits(vec);
-                if _mm256_movemask_epi8(vec) != 0 {
-                    return false;
+                if have_avx2 {
+                    while slice.len() >= 32 {
+                        let vec = _mm256_load_si256(unchecked::get(slice, ..32).as_ptr() as *const __m256i);
+                        if _mm256_movemask_epi8(vec) != 0 {
+                            return false;
+                        }
+                        slice = unchecked::get(slice, 32..);
+                    }
+                    debug_assert!(slice.len() < 32);
                 }
-                slice = slice.get_unchecked(32..);
-            }
-            debug_assert!(slice.len() < 32);
-        }
 
-        if have_sse2 {
-            while slice.len() >= 16 {
-                let vec = u8x16::load_aligned_unchecked(slice.get_unchecked(..16));
-                let vec: __m128i = __m128i::from_bits(vec);
-                if _mm_movemask_epi8(vec) != 0 {
-                    return false;
+                if have_sse2 {
+                    while slice.len() >= 16 {
+                        let vec = _mm_load_si128(unchecked::get(slice, ..16).as_ptr() as *const __m128i);
+                        if _mm_movemask_epi8(vec) != 0 {
+                            return false;
+                        }
+                        slice = unchecked::get(slice, 16..);
+                    }
+                    debug_assert!(slice.len() < 16);
                 }
-                slice = slice.get_unchecked(16..);
-            }
-            debug_assert!(slice.len() < 16);
-        }
 
-        if have_sse {
-            while slice.len() >= 8 {
-                let vec = u8x8::load_aligned_unchecked(slice.get_unchecked(..8));
-                let vec: __m64 = __m64::from_bits(vec);
-                if _mm_movemask_pi8(vec) != 0 {
-                    return false;
+                if have_sse {
+                    while slice.len() >= 8 {
+                        let word = ptr::read(unchecked::get(slice, ..8).as_ptr() as *const u64);
+                        if word & HIGH_BITS != 0 {
+                            return false;
+                        }
+                        slice = unchecked::get(slice, 8..);
+                    }
+                    debug_assert!(slice.len() < 8);
                 }
-                slice = slice.get_unchecked(8..);
+
+                slice.is_ascii()
             }
-            debug_assert!(slice.len() < 8);
         }
-
-        slice.is_ascii()
     }
 }
 
 pub fn is_ascii_simd3(slice: &[u8]) -> bool {
 
-    if !cfg!(target_arch = "x86_64") {
-        return slice.is_ascii();
+    if let Some(level) = autotune::is_ascii_level() {
+        return unsafe { is_ascii_simd3_at(level, slice) };
     }
 
-    // In my experiments on skylake sse2 is faster than avx2 here
-    if cfg!(target_feature = "sse2") || is_x86_feature_detected!("sse2") {
-        return unsafe { is_ascii_simd3_x86_64_sse2(slice) };
-    }
+    #[cfg(simd)]
+    {
+        // In my experiments on skylake sse2 is faster than avx2 here. On
+        // other CPUs, `autotune` finds out.
+        if cfg!(target_feature = "sse2") || is_x86_feature_detected!("sse2") {
+            return unsafe { is_ascii_simd3_x86_64_sse2(slice) };
+        }
 
-    if cfg!(target_feature = "avx2") || is_x86_feature_detected!("avx2") {
-        return unsafe { is_ascii_simd3_x86_64_avx2(slice) };
-    }
+        if cfg!(target_feature = "avx2") || is_x86_feature_detected!("avx2") {
+            return unsafe { is_ascii_simd3_x86_64_avx2(slice) };
+        }
 
-    if cfg!(target_feature = "sse") || is_x86_feature_detected!("sse") {
-        return unsafe { is_ascii_simd3_x86_64_sse(slice) };
+        if cfg!(target_feature = "sse") || is_x86_feature_detected!("sse") {
+            return unsafe { is_ascii_simd3_x86_64_sse(slice) };
+        }
     }
 
-    return slice.is_ascii();
+    slice.is_ascii()
 }
 
-#[cfg(target_arch = "x86_64")]
+/// `is_ascii_simd3` at `level`, which the CPU must have.
+pub unsafe fn is_ascii_simd3_at(level: Level, slice: &[u8]) -> bool {
+    match level {
+        #[cfg(simd)]
+        Level::Avx2 => is_ascii_simd3_x86_64_avx2(slice),
+        #[cfg(simd)]
+        Level::Sse2 => is_ascii_simd3_x86_64_sse2(slice),
+        #[cfg(simd)]
+        Level::Sse => is_ascii_simd3_x86_64_sse(slice),
+        _ => slice.is_ascii(),
+    }
+}
+
+#[cfg(simd)]
+#[target_feature(enable = "avx2")]
 pub unsafe fn is_ascii_simd3_x86_64_avx2(mut slice: &[u8]) -> bool {
-    use std::arch::x86_64::*;
-    use std::simd::u8x32;
-    use std::simd::FromBits;
+    use arch::*;
 
     while slice.len() >= 32 {
-        let vec = u8x32::load_unaligned_unchecked(&slice.get_unchecked(..32));
-        let vec: __m256i = __m256i::from_bits(vec);
+        let vec = _mm256_loadu_si256(unchecked::get(slice, ..32).as_ptr() as *const __m256i);
         if _mm256_movemask_epi8(vec) != 0 {
             return false;
         }
-        slice = &slice.get_unchecked(32..);
+        slice = unchecked::get(slice, 32..);
     }
-    if slice.len() >= 16 {
+    if !slice.is_empty() && overread::can_load(slice.as_ptr(), 32) {
+        let vec = _mm256_loadu_si256(slice.as_ptr() as *const __m256i);
+        _mm256_movemask_epi8(vec) as u32 & overread::lanes(slice.len()) == 0
+    } else if slice.len() >= 16 {
         is_ascii_simd3_x86_64_sse2(slice)
     } else {
         slice.is_ascii()
     }
 }
 
-#[cfg(target_arch = "x86_64")]
+#[cfg(simd)]
+#[target_feature(enable = "sse2")]
 pub unsafe fn is_ascii_simd3_x86_64_sse2(mut slice: &[u8]) -> bool {
-    use std::arch::x86_64::*;
-    use std::simd::u8x16;
-    use std::simd::FromBits;
+    use arch::*;
 
     while slice.len() >= 16 {
-        let vec = u8x16::load_unaligned_unchecked(&slice[..16]);
-        let vec: __m128i = __m128i::from_bits(vec);
+        let vec = _mm_loadu_si128(unchecked::get(slice, ..16).as_ptr() as *const __m128i);
         if _mm_movemask_epi8(vec) != 0 {
             return false;
         }
-        slice = &slice[16..];
+        slice = unchecked::get(slice, 16..);
     }
-    if slice.len() >= 16 {
+    if !slice.is_empty() && overread::can_load(slice.as_ptr(), 16) {
+        let vec = _mm_loadu_si128(slice.as_ptr() as *const __m128i);
+        _mm_movemask_epi8(vec) as u32 & overread::lanes(slice.len()) == 0
+    } else if slice.len() >= 8 {
         is_ascii_simd3_x86_64_sse(slice)
     } else {
         slice.is_ascii()
     }
 }
 
-#[cfg(target_arch = "x86_64")]
+#[cfg(simd)]
 pub unsafe fn is_ascii_simd3_x86_64_sse(mut slice: &[u8]) -> bool {
-    use std::arch::x86_64::*;
-    use std::simd::u8x8;
-    use std::simd::FromBits;
+    use std::ptr;
 
     while slice.len() >= 8 {
-        let vec = u8x8::load_unaligned_unchecked(&slice.get_unchecked(..8));
-        let vec: __m64 = __m64::from_bits(vec);
-        if _mm_movemask_pi8(vec) != 0 {
+        let word = ptr::read_unaligned(unchecked::get(slice, ..8).as_ptr() as *const u64);
+        if word & HIGH_BITS != 0 {
             return false;
         }
-        slice = &slice.get_unchecked(8..);
+        slice = unchecked::get(slice, 8..);
     }
     slice.is_ascii()
 }
 
-#[derive(PartialEq, Eq)]
-pub enum Accel { AVX2, SSE2, SSE, Any }
-
-pub fn is_ascii_auto_simd(slice: &[u8], accel: Accel) -> bool {
-
-    return if cfg!(target_arch = "x86_64") {
-        if (cfg!(target_feature = "avx2") || is_x86_feature_detected!("avx2"))
-            && (accel == Accel::AVX2 || accel == Accel::Any) {
-            #[target_feature(enable = "avx2")]
-            #[inline]
-            unsafe fn go(slice: &[u8]) -> bool {
-                slice.is_ascii()
+// The unrolled kernels OR `$vectors` vectors together and test them with one
+// movemask, so the loop waits on loads instead of on a branch per vector.
+// Rather than finishing byte by byte, the last vector is loaded so it ends at
+// the end of the slice, overlapping bytes that were already checked. Slices
+// shorter than a vector are loaded past their end where that's safe.
+macro_rules! is_ascii_unrolled {
+    ($name:ident, $feature:tt, $vectors:expr, $vec:ty, $load:ident, $or:ident, $movemask:ident, $short:ident) => {
+        #[cfg(simd)]
+        #[target_feature(enable = $feature)]
+        pub unsafe fn $name(slice: &[u8]) -> bool {
+            use std::mem::size_of;
+
+            use arch::*;
+
+            let width = size_of::<$vec>();
+            let block = width * $vectors;
+            let len = slice.len();
+            let ptr = slice.as_ptr();
+            if len < width {
+                if len > 0 && overread::can_load(ptr, width) {
+                    return $movemask($load(ptr as *const $vec)) as u32 & overread::lanes(len) == 0;
+                }
+                return $short(slice);
             }
-            unsafe { go(slice) }
-        } else if (cfg!(target_feature = "sse2") || is_x86_feature_detected!("sse2"))
-            && (accel == Accel::SSE2 || accel == Accel::Any) {
-            #[target_feature(enable = "sse2")]
-            #[inline]
-            unsafe fn go(slice: &[u8]) -> bool {
-                slice.is_ascii()
+
+            let at = |i: usize| unchecked::get(slice, i..i + width).as_ptr() as *const $vec;
+            let mut i = 0;
+            while len - i >= block {
+                let mut acc = $load(at(i));
+                for j in 1..$vectors {
+                    acc = $or(acc, $load(at(i + j * width)));
+                }
+                if $movemask(acc) != 0 {
+                    return false;
+                }
+                i += block;
             }
-            unsafe { go(slice) }
-        } else if (cfg!(target_feature = "sse") || is_x86_feature_detected!("sse"))
-            && (accel == Accel::SSE || accel == Accel::Any) {
-            #[target_feature(enable = "sse")]
-            #[inline]
-            unsafe fn go(slice: &[u8]) -> bool {
-                slice.is_ascii()
+
+            let mut acc = $load(at(len - width));
+            while i + width < len {
+                acc = $or(acc, $load(at(i)));
+                i += width;
             }
-            unsafe { go(slice) }
-        } else {
-            slice.is_ascii()
+            $movemask(acc) == 0
         }
+    };
+}
+
+is_ascii_unrolled!(is_ascii_unrolled_x86_64_avx2_x2, "avx2", 2, __m256i,
+                   _mm256_loadu_si256, _mm256_or_si256, _mm256_movemask_epi8, is_ascii_unrolled_x86_64_sse2_x2);
+is_ascii_unrolled!(is_ascii_unrolled_x86_64_avx2_x4, "avx2", 4, __m256i,
+                   _mm256_loadu_si256, _mm256_or_si256, _mm256_movemask_epi8, is_ascii_unrolled_x86_64_sse2_x2);
+is_ascii_unrolled!(is_ascii_unrolled_x86_64_avx2_x8, "avx2", 8, __m256i,
+                   _mm256_loadu_si256, _mm256_or_si256, _mm256_movemask_epi8, is_ascii_unrolled_x86_64_sse2_x2);
+is_ascii_unrolled!(is_ascii_unrolled_x86_64_sse2_x2, "sse2", 2, __m128i,
+                   _mm_loadu_si128, _mm_or_si128, _mm_movemask_epi8, is_ascii_short);
+is_ascii_unrolled!(is_ascii_unrolled_x86_64_sse2_x4, "sse2", 4, __m128i,
+                   _mm_loadu_si128, _mm_or_si128, _mm_movemask_epi8, is_ascii_short);
+is_ascii_unrolled!(is_ascii_unrolled_x86_64_sse2_x8, "sse2", 8, __m128i,
+                   _mm_loadu_si128, _mm_or_si128, _mm_movemask_epi8, is_ascii_short);
+
+// Fewer than 16 bytes, as two words that overlap in the middle
+#[cfg(simd)]
+#[inline(always)]
+unsafe fn is_ascii_short(slice: &[u8]) -> bool {
+    use std::ptr::read_unaligned;
+
+    let len = slice.len();
+    let at = |i: usize, width: usize| unchecked::get(slice, i..i + width).as_ptr();
+    if len >= 8 {
+        let word = read_unaligned(at(0, 8) as *const u64) | read_unaligned(at(len - 8, 8) as *const u64);
+        word & HIGH_BITS == 0
+    } else if len >= 4 {
+        let word = read_unaligned(at(0, 4) as *const u32) | read_unaligned(at(len - 4, 4) as *const u32);
+        word & 0x8080_8080 == 0
+    } else {
+        slice.iter().fold(0, |acc, &b| acc | b) < 0x80
+    }
+}
+
+#[derive(PartialEq, Eq)]
+pub enum Accel { AVX2, SSE2, SSE, Any }
+
+pub fn is_ascii_auto_simd(slice: &[u8], accel: Accel) -> bool {
+    let level = match accel {
+        Accel::AVX2 => Level::Avx2,
+        Accel::SSE2 => Level::Sse2,
+        Accel::SSE => Level::Sse,
+        Accel::Any => return is_ascii_auto(slice),
+    };
+    if level.available() {
+        unsafe { is_ascii_auto_at(level, slice) }
     } else {
         slice.is_ascii()
     }
 }
 
+multiversion! {
+    fn is_ascii_auto(slice: &[u8]) -> bool {
+        slice.is_ascii()
+    }
+    unsafe fn is_ascii_auto_at(level);
+}
+
+// Not multiversioned, which would put a dispatch in front of both and leave
+// nothing to inline; `is_ascii_auto` is the multiversioned loop
 #[inline(never)]
 pub fn is_ascii_naive_uninlined(buf: &[u8]) -> bool {
     for byte in buf.iter() {
@@ -337,22 +489,167 @@ pub fn is_ascii_naive_inlined(buf: &[u8]) -> bool {
     true
 }
 
-pub fn fast_lines(buf: &str) -> FastLines {
-    FastLines(buf.as_bytes())
+pub fn fast_lines(buf: &str) -> FastLines<'_> {
+    FastLines(fast_byte_lines(buf.as_bytes()))
 }
 
-pub struct FastLines<'a>(&'a [u8]);
+pub struct FastLines<'a>(FastByteLines<'a>);
 
 impl<'a> Iterator for FastLines<'a> {
     type Item = &'a str;
 
-    // TODO: inline vs inline(never)
     fn next(&mut self) -> Option<&'a str> {
+        // Lines are split at ASCII bytes, so each one is still valid UTF-8
+        self.0.next().map(|line| unsafe { unchecked::from_utf8(line) })
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a str> {
+        self.0.nth(n).map(|line| unsafe { unchecked::from_utf8(line) })
+    }
+
+    fn count(self) -> usize {
+        self.0.count()
+    }
+
+    fn last(self) -> Option<&'a str> {
+        self.0.last().map(|line| unsafe { unchecked::from_utf8(line) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// An is_ascii kernel.
+pub type IsAscii = fn(&[u8]) -> bool;
+
+/// Every is_ascii kernel, by the name the benches use. Kernels that need
+/// CPU features this machine doesn't have are `None`.
+pub fn is_ascii_strategies() -> Vec<(&'static str, Option<IsAscii>)> {
+    #[cfg_attr(not(simd), allow(unused_mut))]
+    let mut strategies: Vec<(&'static str, Option<IsAscii>)> = vec![
+        ("std_bytes_closure", Some(|s: &[u8]| s.iter().all(|b| b.is_ascii()))),
+        ("std_bytes", Some(|s: &[u8]| s.is_ascii())),
+        ("simd", Some(is_ascii_simd)),
+        ("simd2", Some(is_ascii_simd2)),
+        ("simd3", Some(is_ascii_simd3)),
+        ("simd3_avx2", None),
+        ("simd3_sse2", None),
+        ("simd3_sse", None),
+        ("unrolled_avx2_x2", None),
+        ("unrolled_avx2_x4", None),
+        ("unrolled_avx2_x8", None),
+        ("unrolled_sse2_x2", None),
+        ("unrolled_sse2_x4", None),
+        ("unrolled_sse2_x8", None),
+        ("auto_simd_avx2", Some(|s: &[u8]| is_ascii_auto_simd(s, Accel::AVX2))),
+        ("auto_simd_sse2", Some(|s: &[u8]| is_ascii_auto_simd(s, Accel::SSE2))),
+        ("auto_simd_sse", Some(|s: &[u8]| is_ascii_auto_simd(s, Accel::SSE))),
+        ("auto_simd_any", Some(|s: &[u8]| is_ascii_auto_simd(s, Accel::Any))),
+        ("naive_uninlined", Some(is_ascii_naive_uninlined)),
+        ("naive_inlined", Some(is_ascii_naive_inlined)),
+    ];
+
+    #[cfg(simd)]
+    {
+        for &mut (name, ref mut kernel) in &mut strategies {
+            *kernel = match name {
+                "simd3_avx2" if is_x86_feature_detected!("avx2") => {
+                    Some(|s: &[u8]| unsafe { is_ascii_simd3_x86_64_avx2(s) })
+                }
+                "simd3_sse2" if is_x86_feature_detected!("sse2") => {
+                    Some(|s: &[u8]| unsafe { is_ascii_simd3_x86_64_sse2(s) })
+                }
+                "simd3_sse" if is_x86_feature_detected!("sse") => {
+                    Some(|s: &[u8]| unsafe { is_ascii_simd3_x86_64_sse(s) })
+                }
+                "unrolled_avx2_x2" if is_x86_feature_detected!("avx2") => {
+                    Some(|s: &[u8]| unsafe { is_ascii_unrolled_x86_64_avx2_x2(s) })
+                }
+                "unrolled_avx2_x4" if is_x86_feature_detected!("avx2") => {
+                    Some(|s: &[u8]| unsafe { is_ascii_unrolled_x86_64_avx2_x4(s) })
+                }
+                "unrolled_avx2_x8" if is_x86_feature_detected!("avx2") => {
+                    Some(|s: &[u8]| unsafe { is_ascii_unrolled_x86_64_avx2_x8(s) })
+                }
+                "unrolled_sse2_x2" if is_x86_feature_detected!("sse2") => {
+                    Some(|s: &[u8]| unsafe { is_ascii_unrolled_x86_64_sse2_x2(s) })
+                }
+                "unrolled_sse2_x4" if is_x86_feature_detected!("sse2") => {
+                    Some(|s: &[u8]| unsafe { is_ascii_unrolled_x86_64_sse2_x4(s) })
+                }
+                "unrolled_sse2_x8" if is_x86_feature_detected!("sse2") => {
+                    Some(|s: &[u8]| unsafe { is_ascii_unrolled_x86_64_sse2_x8(s) })
+                }
+                _ => *kernel,
+            };
+        }
+    }
+
+    strategies
+}
+
+/// Counts the lines `fast_lines` would produce, without producing them.
+pub fn count_lines(buf: &[u8]) -> usize {
+    let newlines = newlines::count_newlines(buf);
+    match buf.last() {
+        Some(&b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
+
+/// Converts `\r\n` and lone `\r` line endings to `\n`, borrowing the
+/// text if it has no `\r`.
+pub fn normalize_line_endings(text: &str) -> Cow<'_, str> {
+    use memchr::memchr;
+
+    match memchr(b'\r', text.as_bytes()) {
+        Some(first) => {
+            let mut buf = text.as_bytes().to_vec();
+            let len = newlines::normalize_line_endings(&mut buf, first);
+            buf.truncate(len);
+            // Only ASCII bytes were removed or replaced
+            Cow::Owned(unsafe { unchecked::string_from_utf8(buf) })
+        }
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Like `normalize_line_endings`, but rewrites the buffer.
+pub fn normalize_line_endings_in_place(buf: &mut Vec<u8>) {
+    use memchr::memchr;
+
+    if let Some(first) = memchr(b'\r', buf) {
+        let len = newlines::normalize_line_endings(buf, first);
+        buf.truncate(len);
+    }
+}
+
+/// Like `fast_lines`, but over bytes that may not be UTF-8.
+pub fn fast_byte_lines(buf: &[u8]) -> FastByteLines<'_> {
+    FastByteLines(buf)
+}
+
+pub struct FastByteLines<'a>(&'a [u8]);
+
+impl<'a> FastByteLines<'a> {
+    /// Decodes each line as it is produced, replacing invalid UTF-8 with
+    /// U+FFFD. Lines that are already valid are borrowed, not copied.
+    pub fn lossy(self) -> LossyLines<'a> {
+        LossyLines(self)
+    }
+}
+
+impl<'a> Iterator for FastByteLines<'a> {
+    type Item = &'a [u8];
+
+    // TODO: inline vs inline(never)
+    fn next(&mut self) -> Option<&'a [u8]> {
         use memchr::memchr;
 
         let slice = &mut self.0;
 
-        if self.0.is_empty() {
+        if slice.is_empty() {
             return None;
         }
 
@@ -360,19 +657,70 @@ impl<'a> Iterator for FastLines<'a> {
 
         unsafe {
             if let Some(i) = memchr(b'\n', slice) {
-                if i > 0 && slice.get_unchecked(i - 1) == &b'\r' {
-                    line = slice.get_unchecked(0..i - 1);
-                    *slice = slice.get_unchecked(i + 1..);
+                if i > 0 && unchecked::get(slice, i - 1) == &b'\r' {
+                    line = unchecked::get(slice, 0..i - 1);
+                    *slice = unchecked::get(slice, i + 1..);
                 } else {
-                    line = slice.get_unchecked(0..i);
-                    *slice = slice.get_unchecked(i + 1..);
+                    line = unchecked::get(slice, 0..i);
+                    *slice = unchecked::get(slice, i + 1..);
                 }
             } else {
-                line = slice;
-                *slice = slice.get_unchecked(0..0);
+                line = *slice;
+                *slice = unchecked::get(slice, 0..0);
             }
+        }
+
+        Some(line)
+    }
 
-            Some(::std::str::from_utf8_unchecked(line))
+    fn nth(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > 0 {
+            // Skip to just past the newline that ends line `n - 1`
+            match newlines::nth_newline(self.0, n - 1) {
+                Some(i) => self.0 = unsafe { unchecked::get(self.0, i + 1..) },
+                None => {
+                    self.0 = &[];
+                    return None;
+                }
+            }
         }
+        self.next()
+    }
+
+    fn count(self) -> usize {
+        count_lines(self.0)
+    }
+
+    fn last(self) -> Option<&'a [u8]> {
+        use memchr::memrchr;
+
+        let slice = self.0;
+        let body = match slice.split_last() {
+            Some((&b'\n', body)) => body,
+            Some(_) => slice,
+            None => return None,
+        };
+        let start = memrchr(b'\n', body).map(|i| i + 1).unwrap_or(0);
+
+        FastByteLines(unsafe { unchecked::get(slice, start..) }).next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Every line but an empty text takes at least one byte
+        if self.0.is_empty() {
+            (0, Some(0))
+        } else {
+            (1, Some(self.0.len()))
+        }
+    }
+}
+
+pub struct LossyLines<'a>(FastByteLines<'a>);
+
+impl<'a> Iterator for LossyLines<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        self.0.next().map(String::from_utf8_lossy)
     }
 }
@@ -0,0 +1,192 @@
+use std::ops::Range;
+
+use newlines::for_each_newline;
+
+/// Maps byte offsets in a text to line/column positions and back.
+///
+/// Lines are numbered from 0 and end after each `\n`, so text ending in a
+/// newline has one more (empty) line than `fast_lines` yields. The index
+/// does not keep the text, only where lines start and where the non-ASCII
+/// chars are, which is enough to convert columns between units.
+#[derive(Clone, Debug)]
+pub struct LineIndex {
+    // Byte offset of the start of every line. Always begins with 0.
+    starts: Vec<usize>,
+    // Every char that is longer than one byte, by offset.
+    wide: Vec<WideChar>,
+    len: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnUnit { Byte, Char, Utf16 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct WideChar {
+    offset: usize,
+    len: usize,
+}
+
+impl WideChar {
+    // How many more bytes than `unit`s this char takes up
+    fn excess(&self, unit: ColumnUnit) -> usize {
+        match unit {
+            ColumnUnit::Byte => 0,
+            ColumnUnit::Char => self.len - 1,
+            ColumnUnit::Utf16 => if self.len == 4 { 2 } else { self.len - 1 },
+        }
+    }
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        LineIndex {
+            starts: line_starts(text, 0),
+            wide: wide_chars(text, 0),
+            len: text.len(),
+        }
+    }
+
+    /// The length in bytes of the indexed text.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Returns `None` if `offset` is past the end of the text, or is
+    /// inside a char when counting in chars or UTF-16 code units.
+    pub fn line_col(&self, offset: usize, unit: ColumnUnit) -> Option<LineCol> {
+        if offset > self.len || (unit != ColumnUnit::Byte && self.splits_char(offset)) {
+            return None;
+        }
+
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let start = self.starts[line];
+        let excess: usize = self.wide_between(start, offset).iter()
+            .map(|w| w.excess(unit))
+            .sum();
+
+        Some(LineCol { line: line, col: offset - start - excess })
+    }
+
+    /// The byte range of a line, including its line terminator.
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        let start = *self.starts.get(line)?;
+        let end = self.starts.get(line + 1).cloned().unwrap_or(self.len);
+        Some(start..end)
+    }
+
+    /// The byte offset of a column within a line. The column may point at
+    /// the line terminator, but not past it.
+    pub fn offset(&self, line: usize, col: usize, unit: ColumnUnit) -> Option<usize> {
+        let range = self.line_range(line)?;
+
+        let mut offset = range.start + col;
+        for w in self.wide_between(range.start, range.end) {
+            if w.offset >= offset {
+                break;
+            }
+            offset += w.excess(unit);
+        }
+
+        let last_line = line + 1 == self.starts.len();
+        if offset > range.end || (offset == range.end && !last_line) {
+            return None;
+        }
+        if self.splits_char(offset) {
+            return None;
+        }
+
+        Some(offset)
+    }
+
+    /// Updates the index after the bytes in `range` have been replaced
+    /// by `new_text`.
+    ///
+    /// # Panics
+    ///
+    /// If `range` is out of bounds of the indexed text, or either end of it
+    /// splits a char.
+    pub fn edit(&mut self, range: Range<usize>, new_text: &str) {
+        assert!(range.start <= range.end && range.end <= self.len,
+                "edit range {:?} out of bounds of text of length {}", range, self.len);
+        assert!(!self.splits_char(range.start) && !self.splits_char(range.end),
+                "edit range {:?} splits a char", range);
+
+        let removed = range.end - range.start;
+        let shift = |offset: usize| offset - removed + new_text.len();
+
+        // A line start is owned by the newline right before it
+        let first = self.starts.binary_search(&(range.start + 1)).unwrap_or_else(|i| i);
+        let last = self.starts.binary_search(&(range.end + 1)).unwrap_or_else(|i| i);
+        let inserted = line_starts(new_text, range.start);
+        let resume = first + inserted.len() - 1;
+        self.starts.splice(first..last, inserted.into_iter().skip(1));
+        for start in &mut self.starts[resume..] {
+            *start = shift(*start);
+        }
+
+        let first = self.wide_index(range.start);
+        let last = self.wide_index(range.end);
+        let inserted = wide_chars(new_text, range.start);
+        let resume = first + inserted.len();
+        self.wide.splice(first..last, inserted);
+        for w in &mut self.wide[resume..] {
+            w.offset = shift(w.offset);
+        }
+
+        self.len = shift(self.len);
+    }
+
+    // The index of the first wide char at or after `offset`
+    fn wide_index(&self, offset: usize) -> usize {
+        match self.wide.binary_search_by_key(&offset, |w| w.offset) {
+            Ok(i) | Err(i) => i,
+        }
+    }
+
+    fn wide_between(&self, start: usize, end: usize) -> &[WideChar] {
+        &self.wide[self.wide_index(start)..self.wide_index(end)]
+    }
+
+    fn splits_char(&self, offset: usize) -> bool {
+        let i = self.wide_index(offset);
+        i > 0 && {
+            let w = self.wide[i - 1];
+            offset < w.offset + w.len
+        }
+    }
+}
+
+// Line starts in `text`, offset by `base`, beginning with `base` itself
+fn line_starts(text: &str, base: usize) -> Vec<usize> {
+    let mut starts = vec![base];
+    for_each_newline(text.as_bytes(), |i| starts.push(base + i + 1));
+    starts
+}
+
+fn wide_chars(text: &str, base: usize) -> Vec<WideChar> {
+    if text.is_ascii() {
+        return Vec::new();
+    }
+
+    text.char_indices()
+        .filter(|&(_, c)| !c.is_ascii())
+        .map(|(i, c)| WideChar { offset: base + i, len: c.len_utf8() })
+        .collect()
+}
@@ -0,0 +1,160 @@
+// The line splitting strategies compared by the `split_lines` benches.
+//
+// All of them are meant to agree with `str::lines`: lines end at `\n`, a
+// `\r` right before the `\n` is dropped, a lone `\r` is part of the line, and
+// the last line needs no terminator.
+
+use memchr::{memchr, memchr2};
+
+use unchecked;
+
+pub type Splitter = fn(&str, &mut dyn FnMut(&str));
+
+pub static STRATEGIES: &[(&str, Splitter)] = &[
+    ("std", std_),
+    ("memchr2", memchr2_),
+    ("memchr2_unchecked", memchr2_unchecked),
+    ("memchr", memchr_),
+    ("memchr_unchecked", memchr_unchecked),
+    ("memchr_iter_unchecked", memchr_iter_unchecked),
+    #[cfg(feature = "nightly")]
+    ("memchr_core_unchecked", memchr_core_unchecked),
+];
+
+// The strategies are generic over the callback so the benches get them
+// inlined. These wrappers are what go in the table.
+
+fn std_(text: &str, f: &mut dyn FnMut(&str)) { split_std(text, f) }
+fn memchr2_(text: &str, f: &mut dyn FnMut(&str)) { split_memchr2(text, f) }
+fn memchr2_unchecked(text: &str, f: &mut dyn FnMut(&str)) { split_memchr2_unchecked(text, f) }
+fn memchr_(text: &str, f: &mut dyn FnMut(&str)) { split_memchr(text, f) }
+fn memchr_unchecked(text: &str, f: &mut dyn FnMut(&str)) { split_memchr_unchecked(text, f) }
+fn memchr_iter_unchecked(text: &str, f: &mut dyn FnMut(&str)) { split_memchr_iter_unchecked(text, f) }
+#[cfg(feature = "nightly")]
+fn memchr_core_unchecked(text: &str, f: &mut dyn FnMut(&str)) { split_memchr_core_unchecked(text, f) }
+
+pub fn split_std<F: FnMut(&str)>(text: &str, mut f: F) {
+    for line in text.lines() {
+        f(line);
+    }
+}
+
+pub fn split_memchr2<F: FnMut(&str)>(text: &str, mut f: F) {
+    let bytes = text.as_bytes();
+    let mut start = 0;
+    let mut search = 0;
+    while start < bytes.len() {
+        if let Some(i) = memchr2(b'\r', b'\n', &bytes[search..]) {
+            let end = search + i;
+            if bytes[end] == b'\n' {
+                f(&text[start..end]);
+                start = end + 1;
+                search = start;
+            } else if bytes.len() > end + 1 && bytes[end + 1] == b'\n' {
+                f(&text[start..end]);
+                start = end + 2;
+                search = start;
+            } else {
+                // A lone '\r' doesn't end the line
+                search = end + 1;
+            }
+        } else {
+            f(&text[start..]);
+            start = bytes.len();
+        }
+    }
+}
+
+pub fn split_memchr2_unchecked<F: FnMut(&str)>(text: &str, mut f: F) {
+    unsafe {
+        let bytes = text.as_bytes();
+        let mut start = 0;
+        let mut search = 0;
+        while start < bytes.len() {
+            if let Some(i) = memchr2(b'\r', b'\n', unchecked::get(bytes, search..)) {
+                let end = search + i;
+                if unchecked::get(bytes, end) == &b'\n' {
+                    f(unchecked::get_str(text, start..end));
+                    start = end + 1;
+                    search = start;
+                } else if bytes.len() > end + 1 && unchecked::get(bytes, end + 1) == &b'\n' {
+                    f(unchecked::get_str(text, start..end));
+                    start = end + 2;
+                    search = start;
+                } else {
+                    search = end + 1;
+                }
+            } else {
+                f(unchecked::get_str(text, start..));
+                start = bytes.len();
+            }
+        }
+    }
+}
+
+pub fn split_memchr<F: FnMut(&str)>(text: &str, mut f: F) {
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(i) = memchr(b'\n', rest.as_bytes()) {
+            if i > 0 && rest.as_bytes()[i - 1] == b'\r' {
+                f(&rest[0..i - 1]);
+            } else {
+                f(&rest[0..i]);
+            }
+            rest = &rest[i + 1..];
+        } else {
+            f(rest);
+            rest = "";
+        }
+    }
+}
+
+pub fn split_memchr_unchecked<F: FnMut(&str)>(text: &str, mut f: F) {
+    unsafe {
+        let mut slice = text.as_bytes();
+        while !slice.is_empty() {
+            let line;
+            if let Some(i) = memchr(b'\n', slice) {
+                if i > 0 && unchecked::get(slice, i - 1) == &b'\r' {
+                    line = unchecked::get(slice, 0..i - 1);
+                } else {
+                    line = unchecked::get(slice, 0..i);
+                }
+                slice = unchecked::get(slice, i + 1..);
+            } else {
+                line = slice;
+                slice = &[];
+            }
+            f(unchecked::from_utf8(line));
+        }
+    }
+}
+
+pub fn split_memchr_iter_unchecked<F: FnMut(&str)>(text: &str, mut f: F) {
+    for line in ::fast_lines(text) {
+        f(line);
+    }
+}
+
+/// Needs the `nightly` feature, for the standard library's internal `memchr`.
+#[cfg(feature = "nightly")]
+pub fn split_memchr_core_unchecked<F: FnMut(&str)>(text: &str, mut f: F) {
+    unsafe {
+        let mut slice = text.as_bytes();
+        while !slice.is_empty() {
+            let line;
+            if let Some(i) = ::core::slice::memchr::memchr(b'\n', slice) {
+                if i > 0 && unchecked::get(slice, i - 1) == &b'\r' {
+                    line = unchecked::get(slice, 0..i - 1);
+                } else {
+                    line = unchecked::get(slice, 0..i);
+                }
+                slice = unchecked::get(slice, i + 1..);
+            } else {
+                line = slice;
+                slice = &[];
+            }
+            f(unchecked::from_utf8(line));
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// The benchmark manifest format.
+//
+// A manifest is a list of groups. Each group names a fixture, what to look
+// for in it, the result every strategy must produce, and the strategies to
+// run:
+//
+//     # Comments start with '#'
+//     [find_set_of_bytes_early]
+//     fixture = lipsum-emph.md
+//     bytes = #_*=-~|[\\>^`&/:@
+//     expect = 419
+//     strategies = jetscii_bytes open_table
+//
+// The search is one of `bytes = <set>`, `needle = <text>`, `is_ascii` or
+// `lines`, and `expect` is an offset or `none`, `true` or `false`, or a line
+// count to match. Values run to the end of the line with surrounding
+// whitespace trimmed, and `bytes` and `needle` take the escapes `\\`, `\n`,
+// `\r`, `\t`, `\s` (space) and `\xNN`. A long list of strategies can be
+// split over several `strategies` lines.
+//
+// `build.rs` includes this file to generate the bench for each strategy, so
+// it must only use `std`.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+    pub groups: Vec<Group>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub fixture: String,
+    pub search: Search,
+    pub expect: Outcome,
+    pub strategies: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Search {
+    ByteSet(Vec<u8>),
+    Substring(String),
+    IsAscii,
+    Lines,
+}
+
+/// What running a strategy over a fixture produces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Position(Option<usize>),
+    Bool(bool),
+    Count(usize),
+}
+
+impl Outcome {
+    /// True if this is what a strategy should produce where `expected` is.
+    /// A strategy that only says whether there's a match answers a position
+    /// by saying whether there is one.
+    pub fn satisfies(self, expected: Outcome) -> bool {
+        match (self, expected) {
+            (Outcome::Bool(found), Outcome::Position(position)) => found == position.is_some(),
+            _ => self == expected,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Manifest {
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+}
+
+// A group whose keys are still being read
+#[derive(Default)]
+struct Partial {
+    name: String,
+    line: usize,
+    fixture: Option<String>,
+    search: Option<Search>,
+    expect: Option<String>,
+    strategies: Option<Vec<String>>,
+}
+
+pub fn parse(text: &str) -> Result<Manifest, ParseError> {
+    let mut groups = Vec::new();
+    let mut current: Option<Partial> = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let lineno = i + 1;
+        let err = |message: String| ParseError { line: lineno, message: message };
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return Err(err("unterminated group header".to_string()));
+            }
+            let name = line[1..line.len() - 1].trim();
+            check_ident(name).map_err(&err)?;
+            if groups.iter().any(|g: &Group| g.name == name)
+                || current.as_ref().is_some_and(|c| c.name == name)
+            {
+                return Err(err(format!("duplicate group {}", name)));
+            }
+            if let Some(partial) = current.take() {
+                groups.push(finish(partial)?);
+            }
+            current = Some(Partial { name: name.to_string(), line: lineno, ..Partial::default() });
+            continue;
+        }
+
+        let group = match current {
+            Some(ref mut group) => group,
+            None => return Err(err("key outside of a group".to_string())),
+        };
+
+        let (key, value) = match line.find('=') {
+            Some(eq) => (line[..eq].trim(), Some(line[eq + 1..].trim())),
+            None => (line, None),
+        };
+
+        let search = match (key, value) {
+            ("fixture", Some(value)) => {
+                group.fixture = Some(value.to_string());
+                None
+            }
+            ("expect", Some(value)) => {
+                group.expect = Some(value.to_string());
+                None
+            }
+            ("strategies", Some(value)) => {
+                let names: Vec<String> = value.split_whitespace().map(|s| s.to_string()).collect();
+                for name in &names {
+                    check_ident(name).map_err(&err)?;
+                }
+                group.strategies.get_or_insert_with(Vec::new).extend(names);
+                None
+            }
+            ("bytes", Some(value)) => Some(Search::ByteSet(unescape(value).map_err(&err)?)),
+            ("needle", Some(value)) => {
+                let needle = String::from_utf8(unescape(value).map_err(&err)?)
+                    .map_err(|_| err("needle is not UTF-8".to_string()))?;
+                Some(Search::Substring(needle))
+            }
+            ("is_ascii", None) => Some(Search::IsAscii),
+            ("lines", None) => Some(Search::Lines),
+            _ => return Err(err(format!("unexpected line '{}'", line))),
+        };
+
+        if let Some(search) = search {
+            if group.search.is_some() {
+                return Err(err("group has more than one search".to_string()));
+            }
+            group.search = Some(search);
+        }
+    }
+
+    if let Some(partial) = current.take() {
+        groups.push(finish(partial)?);
+    }
+
+    Ok(Manifest { groups: groups })
+}
+
+fn finish(partial: Partial) -> Result<Group, ParseError> {
+    let line = partial.line;
+    let name = partial.name;
+    let missing = |what: &str| ParseError {
+        line: line,
+        message: format!("group {} has no {}", name, what),
+    };
+
+    let fixture = partial.fixture.ok_or_else(|| missing("fixture"))?;
+    let search = partial.search.ok_or_else(|| missing("search"))?;
+    let expect = partial.expect.ok_or_else(|| missing("expect"))?;
+    let strategies = partial.strategies.ok_or_else(|| missing("strategies"))?;
+
+    let expect = match search {
+        Search::ByteSet(_) | Search::Substring(_) => match &expect[..] {
+            "none" => Some(Outcome::Position(None)),
+            n => n.parse().ok().map(|n| Outcome::Position(Some(n))),
+        },
+        Search::IsAscii => expect.parse().ok().map(Outcome::Bool),
+        Search::Lines => expect.parse().ok().map(Outcome::Count),
+    };
+    let expect = expect.ok_or_else(|| ParseError {
+        line: line,
+        message: format!("group {} has an expected result that doesn't fit its search", name),
+    })?;
+
+    Ok(Group {
+        name: name,
+        fixture: fixture,
+        search: search,
+        expect: expect,
+        strategies: strategies,
+    })
+}
+
+// Group and strategy names become Rust item names
+fn check_ident(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(c) => (c.is_ascii_lowercase() || c == '_')
+            && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a valid name", name))
+    }
+}
+
+fn unescape(value: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut rest = value.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let (&escape, tail) = rest.split_first().ok_or("'\\' at end of value")?;
+        rest = tail;
+        bytes.push(match escape {
+            b'\\' => b'\\',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b's' => b' ',
+            b'x' if rest.len() >= 2 => {
+                let hex = ::std::str::from_utf8(&rest[..2]).ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or("bad '\\x' escape")?;
+                rest = &rest[2..];
+                hex
+            }
+            _ => return Err(format!("unknown escape '\\{}'", escape as char)),
+        });
+    }
+
+    Ok(bytes)
+}
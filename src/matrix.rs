@@ -0,0 +1,129 @@
+// Runs the benchmark manifest: every strategy of every group, over the
+// group's fixture. `build.rs` turns each of these cells into a `#[bench]`
+// and a `#[test]`.
+
+use std::borrow::Cow;
+
+use byteset;
+use fixtures;
+use harness::black_box;
+use line_splitters;
+use manifest::{self, Group, Manifest, Outcome, Search};
+use substring;
+
+pub static MANIFEST: &str = include_str!("benches.manifest");
+
+pub fn manifest() -> Manifest {
+    manifest::parse(MANIFEST).expect("benches.manifest was checked by build.rs")
+}
+
+/// The strategies that need the `nightly` feature. Without it they aren't in
+/// their registries, and the manifest's cells for them can't run.
+pub static NIGHTLY_ONLY: &[&str] = &["memchr_std", "memchr_core_unchecked"];
+
+/// True if `strategy` is in its registry in this build.
+pub fn built(strategy: &str) -> bool {
+    cfg!(feature = "nightly") || !NIGHTLY_ONLY.contains(&strategy)
+}
+
+/// One search, set up and ready to repeat.
+pub type Runner<'a> = Box<dyn FnMut() -> Outcome + 'a>;
+
+/// Builds whatever `strategy` needs to search `group`'s fixture.
+///
+/// Returns `Ok(None)` if the strategy can't run here, because the CPU lacks
+/// the features it needs, it wasn't built, or it can't search for what the
+/// group asks, and an error if the manifest names something that doesn't exist.
+pub fn prepare<'a>(group: &'a Group, strategy: &str) -> Result<Option<Runner<'a>>, String> {
+    let text = fixtures::fixture(&group.fixture)
+        .ok_or_else(|| format!("no fixture named {}", group.fixture))?;
+    prepare_text(group, strategy, text)
+}
+
+/// Like `prepare`, but searching `text` instead of the group's fixture.
+pub fn prepare_text<'a>(group: &'a Group, strategy: &str, text: &'a str)
+                        -> Result<Option<Runner<'a>>, String> {
+    let unknown = || format!("group {} has no strategy named {}", group.name, strategy);
+    if !built(strategy) {
+        return Ok(None);
+    }
+
+    match group.search {
+        Search::ByteSet(ref set) => {
+            let build = lookup(byteset::STRATEGIES, strategy).ok_or_else(unknown)?;
+            Ok(build(set).map(|searcher| {
+                Box::new(move || Outcome::Position(searcher.find(text))) as Runner
+            }))
+        }
+        Search::Substring(ref needle) => {
+            if let Some(contains) = lookup(substring::CONTAINS, strategy) {
+                return Ok(Some(Box::new(move || Outcome::Bool(contains(text, needle)))));
+            }
+            let build = lookup(substring::STRATEGIES, strategy).ok_or_else(unknown)?;
+            let searcher = build(needle);
+            Ok(Some(Box::new(move || Outcome::Position(searcher.find(text)))))
+        }
+        Search::IsAscii => {
+            let kernel = lookup(&::is_ascii_strategies(), strategy).ok_or_else(unknown)?;
+            Ok(kernel.map(|kernel| {
+                Box::new(move || Outcome::Bool(kernel(text.as_bytes()))) as Runner
+            }))
+        }
+        Search::Lines => {
+            let split = lookup(line_splitters::STRATEGIES, strategy).ok_or_else(unknown)?;
+            Ok(Some(Box::new(move || {
+                let mut lines = 0;
+                split(text, &mut |line| {
+                    lines += 1;
+                    black_box(line);
+                });
+                Outcome::Count(lines)
+            })))
+        }
+    }
+}
+
+/// Runs a cell once and compares the outcome with the one the manifest
+/// expects. Cells that can't run here pass.
+pub fn check(group: &Group, strategy: &str) -> Result<(), String> {
+    let mut run = match prepare(group, strategy)? {
+        Some(run) => run,
+        None => return Ok(()),
+    };
+
+    let outcome = run();
+    if outcome.satisfies(group.expect) {
+        Ok(())
+    } else {
+        Err(format!("{}::{} produced {:?} instead of {:?}{}",
+                    group.name, strategy, outcome, group.expect, describe(group, outcome)))
+    }
+}
+
+/// What every strategy for `search` should produce over `text`, worked out
+/// the slow way. For text that has no expected outcome in the manifest.
+pub fn reference(search: &Search, text: &str) -> Outcome {
+    match *search {
+        Search::ByteSet(ref set) => {
+            Outcome::Position(text.bytes().position(|b| set.contains(&b)))
+        }
+        Search::Substring(ref needle) => Outcome::Position(text.find(&needle[..])),
+        Search::IsAscii => Outcome::Bool(text.is_ascii()),
+        Search::Lines => Outcome::Count(text.lines().count()),
+    }
+}
+
+// Shows what's at a wrong position, to tell a miscount from a wrong match
+fn describe(group: &Group, outcome: Outcome) -> Cow<'static, str> {
+    match (outcome, fixtures::fixture(&group.fixture)) {
+        (Outcome::Position(Some(i)), Some(text)) if i < text.len() => {
+            format!(" (byte {:?})", text.as_bytes()[i] as char).into()
+        }
+        _ => "".into(),
+    }
+}
+
+/// Finds a strategy in one of the registries by name.
+pub fn lookup<T: Copy>(strategies: &[(&str, T)], name: &str) -> Option<T> {
+    strategies.iter().find(|&&(n, _)| n == name).map(|&(_, t)| t)
+}
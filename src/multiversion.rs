@@ -0,0 +1,111 @@
+// Compiles one plain function several times, once for each vector extension,
+// so LLVM can autovectorise each clone with the widest registers it's
+// allowed, and calls the best clone the CPU can run.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use autotune::Level;
+
+// From best to worst
+static LEVELS: &[Level] = &[Level::Avx2, Level::Sse2, Level::Sse, Level::Scalar];
+
+// One more than the index in `LEVELS` of the best level, or 0 until it's been
+// looked for
+static BEST: AtomicUsize = AtomicUsize::new(0);
+
+/// The best level the CPU has, found on the first call.
+#[inline]
+pub fn best() -> Level {
+    match BEST.load(Ordering::Relaxed) {
+        0 => {
+            let i = LEVELS.iter().position(|level| level.available()).unwrap();
+            BEST.store(i + 1, Ordering::Relaxed);
+            LEVELS[i]
+        }
+        i => LEVELS[i - 1],
+    }
+}
+
+/// Defines a function with an avx2, sse2, sse and baseline clone of its
+/// body, and a dispatcher that calls the best one the CPU has. The function
+/// can't be generic, and its arguments must be plain names. Like the other
+/// kernels, the vector clones are only built under `cfg(simd)`, which
+/// `build.rs` sets for this crate alone, so the macro isn't exported.
+///
+/// ```ignore
+/// multiversion! {
+///     pub fn sum(bytes: &[u8]) -> u64 {
+///         bytes.iter().map(|&b| b as u64).sum()
+///     }
+/// }
+/// ```
+///
+/// A second, `unsafe` function that takes the level to run at, which the CPU
+/// must have, can be asked for after the body:
+///
+/// ```ignore
+/// multiversion! {
+///     pub fn sum(bytes: &[u8]) -> u64 {
+///         bytes.iter().map(|&b| b as u64).sum()
+///     }
+///     pub unsafe fn sum_at(level);
+/// }
+/// ```
+macro_rules! multiversion {
+    (@at $at:ident ($($head:tt)*) ($($arg:ident: $ty:ty),*) -> $ret:ty $body:block) => {
+        $($head)* unsafe fn $at(level: $crate::autotune::Level, $($arg: $ty),*) -> $ret {
+            #[cfg(simd)]
+            #[target_feature(enable = "avx2")]
+            #[inline]
+            unsafe fn avx2($($arg: $ty),*) -> $ret $body
+
+            #[cfg(simd)]
+            #[target_feature(enable = "sse2")]
+            #[inline]
+            unsafe fn sse2($($arg: $ty),*) -> $ret $body
+
+            #[cfg(simd)]
+            #[target_feature(enable = "sse")]
+            #[inline]
+            unsafe fn sse($($arg: $ty),*) -> $ret $body
+
+            #[inline]
+            fn baseline($($arg: $ty),*) -> $ret $body
+
+            match level {
+                #[cfg(simd)]
+                $crate::autotune::Level::Avx2 => avx2($($arg),*),
+                #[cfg(simd)]
+                $crate::autotune::Level::Sse2 => sse2($($arg),*),
+                #[cfg(simd)]
+                $crate::autotune::Level::Sse => sse($($arg),*),
+                _ => baseline($($arg),*),
+            }
+        }
+    };
+
+    (
+        $(#[$attr:meta])*
+        $vis:vis fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty $body:block
+    ) => {
+        $(#[$attr])*
+        $vis fn $name($($arg: $ty),*) -> $ret {
+            multiversion!(@at at () ($($arg: $ty),*) -> $ret $body);
+            unsafe { at($crate::multiversion::best(), $($arg),*) }
+        }
+    };
+
+    (
+        $(#[$attr:meta])*
+        $vis:vis fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty $body:block
+        $(#[$at_attr:meta])*
+        $at_vis:vis unsafe fn $at:ident(level);
+    ) => {
+        $(#[$attr])*
+        $vis fn $name($($arg: $ty),*) -> $ret {
+            unsafe { $at($crate::multiversion::best(), $($arg),*) }
+        }
+
+        multiversion!(@at $at ($(#[$at_attr])* $at_vis) ($($arg: $ty),*) -> $ret $body);
+    };
+}
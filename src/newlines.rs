@@ -0,0 +1,376 @@
+// SIMD kernels for finding `\n` bytes, shared by the line APIs.
+
+use autotune::{self, Level};
+#[cfg(simd)]
+use overread;
+use unchecked;
+
+// The level to run at: the one `autotune` picked, or the best the CPU has
+#[inline]
+fn level() -> Level {
+    if let Some(level) = autotune::newline_level() {
+        return level;
+    }
+
+    #[cfg(simd)]
+    {
+        if cfg!(target_feature = "avx2") || is_x86_feature_detected!("avx2") {
+            return Level::Avx2;
+        }
+
+        if cfg!(target_feature = "sse2") || is_x86_feature_detected!("sse2") {
+            return Level::Sse2;
+        }
+    }
+
+    Level::Scalar
+}
+
+/// Calls `f` with the index of every `\n` in `buf`, in order.
+#[inline]
+pub fn for_each_newline<F: FnMut(usize)>(buf: &[u8], f: F) {
+    match level() {
+        #[cfg(simd)]
+        Level::Avx2 => unsafe { for_each_newline_x86_64_avx2(buf, f) },
+        #[cfg(simd)]
+        Level::Sse2 => unsafe { for_each_newline_x86_64_sse2(buf, f) },
+        _ => for_each_newline_scalar(buf, f),
+    }
+}
+
+fn for_each_newline_scalar<F: FnMut(usize)>(buf: &[u8], mut f: F) {
+    for (i, byte) in buf.iter().enumerate() {
+        if *byte == b'\n' {
+            f(i);
+        }
+    }
+}
+
+#[cfg(simd)]
+#[target_feature(enable = "avx2")]
+unsafe fn for_each_newline_x86_64_avx2<F: FnMut(usize)>(buf: &[u8], mut f: F) {
+    use arch::*;
+
+    let newline = _mm256_set1_epi8(b'\n' as i8);
+    let mut i = 0;
+
+    while buf.len() - i >= 32 {
+        let vec = _mm256_loadu_si256(unchecked::get(buf, i..i + 32).as_ptr() as *const __m256i);
+        let mut mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(vec, newline)) as u32;
+        while mask != 0 {
+            f(i + mask.trailing_zeros() as usize);
+            mask &= mask - 1;
+        }
+        i += 32;
+    }
+
+    for_each_newline_x86_64_sse2(unchecked::get(buf, i..), |j| f(i + j))
+}
+
+#[cfg(simd)]
+#[target_feature(enable = "sse2")]
+unsafe fn for_each_newline_x86_64_sse2<F: FnMut(usize)>(buf: &[u8], mut f: F) {
+    use arch::*;
+
+    let newline = _mm_set1_epi8(b'\n' as i8);
+    let mut i = 0;
+
+    while buf.len() - i >= 16 {
+        let vec = _mm_loadu_si128(unchecked::get(buf, i..i + 16).as_ptr() as *const __m128i);
+        let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(vec, newline)) as u32;
+        while mask != 0 {
+            f(i + mask.trailing_zeros() as usize);
+            mask &= mask - 1;
+        }
+        i += 16;
+    }
+
+    if i < buf.len() && overread::can_load(unchecked::get(buf, i..).as_ptr(), 16) {
+        let vec = _mm_loadu_si128(unchecked::get(buf, i..).as_ptr() as *const __m128i);
+        let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(vec, newline)) as u32 & overread::lanes(buf.len() - i);
+        while mask != 0 {
+            f(i + mask.trailing_zeros() as usize);
+            mask &= mask - 1;
+        }
+        return;
+    }
+
+    for_each_newline_scalar(unchecked::get(buf, i..), |j| f(i + j))
+}
+
+/// Counts the `\n` bytes in `buf`.
+#[inline]
+pub fn count_newlines(buf: &[u8]) -> usize {
+    unsafe { count_newlines_at(level(), buf) }
+}
+
+/// `count_newlines` at `level`, which the CPU must have.
+pub unsafe fn count_newlines_at(level: Level, buf: &[u8]) -> usize {
+    match level {
+        #[cfg(simd)]
+        Level::Avx2 => count_newlines_x86_64_avx2(buf),
+        #[cfg(simd)]
+        Level::Sse2 => count_newlines_x86_64_sse2(buf),
+        _ => count_newlines_scalar(buf),
+    }
+}
+
+// At the scalar level, whether `autotune` picked it or the hand-written
+// kernels can't run here, the compiler's own vectorising is the next best
+// thing
+multiversion! {
+    fn count_newlines_scalar(buf: &[u8]) -> usize {
+        buf.iter().filter(|byte| **byte == b'\n').count()
+    }
+}
+
+// For the few bytes the vector kernels leave over, which aren't worth a
+// dispatch
+#[cfg(simd)]
+fn count_newlines_tail(buf: &[u8]) -> usize {
+    buf.iter().filter(|byte| **byte == b'\n').count()
+}
+
+// The vector kernels keep a count per byte lane, subtracting the all-ones
+// compare result from it, and fold the lanes into the total with `sad`
+// before any of them can overflow.
+
+#[cfg(simd)]
+#[target_feature(enable = "avx2")]
+unsafe fn count_newlines_x86_64_avx2(buf: &[u8]) -> usize {
+    use arch::*;
+
+    let newline = _mm256_set1_epi8(b'\n' as i8);
+    let mut count = 0;
+    let mut i = 0;
+
+    while buf.len() - i >= 32 {
+        let mut lanes = _mm256_setzero_si256();
+        let mut rounds = 0;
+        while rounds < 255 && buf.len() - i >= 32 {
+            let vec = _mm256_loadu_si256(unchecked::get(buf, i..i + 32).as_ptr() as *const __m256i);
+            lanes = _mm256_sub_epi8(lanes, _mm256_cmpeq_epi8(vec, newline));
+            i += 32;
+            rounds += 1;
+        }
+
+        let mut sums = [0u64; 4];
+        let vec = _mm256_sad_epu8(lanes, _mm256_setzero_si256());
+        _mm256_storeu_si256(sums.as_mut_ptr() as *mut __m256i, vec);
+        count += (sums[0] + sums[1] + sums[2] + sums[3]) as usize;
+    }
+
+    count + count_newlines_x86_64_sse2(unchecked::get(buf, i..))
+}
+
+#[cfg(simd)]
+#[target_feature(enable = "sse2")]
+unsafe fn count_newlines_x86_64_sse2(buf: &[u8]) -> usize {
+    use arch::*;
+
+    let newline = _mm_set1_epi8(b'\n' as i8);
+    let mut count = 0;
+    let mut i = 0;
+
+    while buf.len() - i >= 16 {
+        let mut lanes = _mm_setzero_si128();
+        let mut rounds = 0;
+        while rounds < 255 && buf.len() - i >= 16 {
+            let vec = _mm_loadu_si128(unchecked::get(buf, i..i + 16).as_ptr() as *const __m128i);
+            lanes = _mm_sub_epi8(lanes, _mm_cmpeq_epi8(vec, newline));
+            i += 16;
+            rounds += 1;
+        }
+
+        let mut sums = [0u64; 2];
+        let vec = _mm_sad_epu8(lanes, _mm_setzero_si128());
+        _mm_storeu_si128(sums.as_mut_ptr() as *mut __m128i, vec);
+        count += (sums[0] + sums[1]) as usize;
+    }
+
+    if i < buf.len() && overread::can_load(unchecked::get(buf, i..).as_ptr(), 16) {
+        let vec = _mm_loadu_si128(unchecked::get(buf, i..).as_ptr() as *const __m128i);
+        let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(vec, newline)) as u32 & overread::lanes(buf.len() - i);
+        return count + mask.count_ones() as usize;
+    }
+
+    count + count_newlines_tail(unchecked::get(buf, i..))
+}
+
+/// The index of the `n`th (from 0) `\n` in `buf`.
+///
+/// Whole blocks are skipped by counting the newlines in them, and only the
+/// block holding the target is searched bit by bit.
+#[inline]
+pub fn nth_newline(buf: &[u8], n: usize) -> Option<usize> {
+    unsafe { nth_newline_at(level(), buf, n) }
+}
+
+/// `nth_newline` at `level`, which the CPU must have.
+pub unsafe fn nth_newline_at(level: Level, buf: &[u8], n: usize) -> Option<usize> {
+    match level {
+        #[cfg(simd)]
+        Level::Avx2 => nth_newline_x86_64_avx2(buf, n),
+        #[cfg(simd)]
+        Level::Sse2 => nth_newline_x86_64_sse2(buf, n),
+        _ => nth_newline_scalar(buf, n),
+    }
+}
+
+multiversion! {
+    fn nth_newline_scalar(buf: &[u8], n: usize) -> Option<usize> {
+        buf.iter()
+            .enumerate()
+            .filter(|&(_, byte)| *byte == b'\n')
+            .nth(n)
+            .map(|(i, _)| i)
+    }
+}
+
+// Like `count_newlines_tail`
+#[cfg(simd)]
+fn nth_newline_tail(buf: &[u8], n: usize) -> Option<usize> {
+    buf.iter()
+        .enumerate()
+        .filter(|&(_, byte)| *byte == b'\n')
+        .nth(n)
+        .map(|(i, _)| i)
+}
+
+// Index of the `n`th set bit of `mask`, which has more than `n` set bits
+#[cfg(simd)]
+#[inline(always)]
+fn nth_bit(mut mask: u32, n: usize) -> usize {
+    for _ in 0..n {
+        mask &= mask - 1;
+    }
+    mask.trailing_zeros() as usize
+}
+
+#[cfg(simd)]
+#[target_feature(enable = "avx2")]
+unsafe fn nth_newline_x86_64_avx2(buf: &[u8], mut n: usize) -> Option<usize> {
+    use arch::*;
+
+    let newline = _mm256_set1_epi8(b'\n' as i8);
+    let mut i = 0;
+
+    while buf.len() - i >= 32 {
+        let vec = _mm256_loadu_si256(unchecked::get(buf, i..i + 32).as_ptr() as *const __m256i);
+        let mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(vec, newline)) as u32;
+        let count = mask.count_ones() as usize;
+        if n < count {
+            return Some(i + nth_bit(mask, n));
+        }
+        n -= count;
+        i += 32;
+    }
+
+    nth_newline_x86_64_sse2(unchecked::get(buf, i..), n).map(|j| i + j)
+}
+
+#[cfg(simd)]
+#[target_feature(enable = "sse2")]
+unsafe fn nth_newline_x86_64_sse2(buf: &[u8], mut n: usize) -> Option<usize> {
+    use arch::*;
+
+    let newline = _mm_set1_epi8(b'\n' as i8);
+    let mut i = 0;
+
+    while buf.len() - i >= 16 {
+        let vec = _mm_loadu_si128(unchecked::get(buf, i..i + 16).as_ptr() as *const __m128i);
+        let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(vec, newline)) as u32;
+        let count = mask.count_ones() as usize;
+        if n < count {
+            return Some(i + nth_bit(mask, n));
+        }
+        n -= count;
+        i += 16;
+    }
+
+    if i < buf.len() && overread::can_load(unchecked::get(buf, i..).as_ptr(), 16) {
+        let vec = _mm_loadu_si128(unchecked::get(buf, i..).as_ptr() as *const __m128i);
+        let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(vec, newline)) as u32 & overread::lanes(buf.len() - i);
+        return if n < mask.count_ones() as usize { Some(i + nth_bit(mask, n)) } else { None };
+    }
+
+    nth_newline_tail(unchecked::get(buf, i..), n).map(|j| i + j)
+}
+
+/// Rewrites `buf[from..]` in place so that `\r\n` and lone `\r` become
+/// `\n`, returning the new length of `buf`.
+///
+/// Runs without a `\r` are moved a vector at a time.
+#[inline]
+pub fn normalize_line_endings(buf: &mut [u8], from: usize) -> usize {
+    assert!(from <= buf.len());
+
+    #[cfg(simd)]
+    {
+        if cfg!(target_feature = "sse2") || is_x86_feature_detected!("sse2") {
+            return unsafe { normalize_line_endings_x86_64_sse2(buf, from) };
+        }
+    }
+
+    unsafe { normalize_line_endings_scalar(buf, from, from) }
+}
+
+// Everything before `write` is done, and `read` is where to continue.
+// The output never gets ahead of the input, so `write <= read`.
+unsafe fn normalize_line_endings_scalar(buf: &mut [u8], mut read: usize, mut write: usize) -> usize {
+    let len = buf.len();
+
+    while read < len {
+        let byte = *unchecked::get(buf, read);
+        read += 1;
+        if byte == b'\r' {
+            *unchecked::get_mut(buf, write) = b'\n';
+            if read < len && *unchecked::get(buf, read) == b'\n' {
+                read += 1;
+            }
+        } else {
+            *unchecked::get_mut(buf, write) = byte;
+        }
+        write += 1;
+    }
+
+    write
+}
+
+#[cfg(simd)]
+#[target_feature(enable = "sse2")]
+unsafe fn normalize_line_endings_x86_64_sse2(buf: &mut [u8], from: usize) -> usize {
+    use std::ptr;
+
+    use arch::*;
+
+    let cr = _mm_set1_epi8(b'\r' as i8);
+    let len = buf.len();
+    let mut read = from;
+    let mut write = from;
+
+    while len - read >= 16 {
+        let vec = _mm_loadu_si128(unchecked::get(buf, read..read + 16).as_ptr() as *const __m128i);
+        let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(vec, cr)) as u32;
+        if mask == 0 {
+            // Only ever overwrites bytes that are already in `vec`
+            _mm_storeu_si128(unchecked::get_mut(buf, write..write + 16).as_mut_ptr() as *mut __m128i, vec);
+            read += 16;
+            write += 16;
+            continue;
+        }
+
+        let run = mask.trailing_zeros() as usize;
+        ptr::copy(unchecked::get(buf, read..read + run).as_ptr(),
+                  unchecked::get_mut(buf, write..write + run).as_mut_ptr(), run);
+        read += run + 1;
+        write += run;
+        *unchecked::get_mut(buf, write) = b'\n';
+        write += 1;
+        if read < len && *unchecked::get(buf, read) == b'\n' {
+            read += 1;
+        }
+    }
+
+    normalize_line_endings_scalar(buf, read, write)
+}
@@ -0,0 +1,32 @@
+// Loading a whole vector for the last few bytes of a slice, instead of
+// finishing them a byte at a time or with narrower vectors.
+//
+// A load that runs past the end of a slice can only fault if it runs onto a
+// page that isn't mapped, and memory is mapped a page at a time, so a load
+// that ends on the page it starts on is as safe as far as the CPU goes. The
+// lanes past the end hold whatever is there, and the callers mask them out.
+//
+// It isn't safe as far as Rust's memory model goes, though, and Miri or a
+// sanitizer would rightly say so, so under Miri or with the `checked` feature
+// no load is allowed and the callers take their scalar paths instead.
+
+/// The smallest page size of any target with vector kernels.
+pub const PAGE: usize = 4096;
+
+/// True if `width` bytes can be loaded from `ptr` without crossing into the
+/// next page, however few of them are in the slice.
+#[inline(always)]
+pub fn can_load(ptr: *const u8, width: usize) -> bool {
+    !cfg!(miri) && !cfg!(feature = "checked") && (ptr as usize & (PAGE - 1)) <= PAGE - width
+}
+
+/// The movemask bits of the first `len` lanes of a vector.
+#[inline(always)]
+pub fn lanes(len: usize) -> u32 {
+    debug_assert!(len <= 32);
+    if len >= 32 {
+        !0
+    } else {
+        (1 << len) - 1
+    }
+}
@@ -0,0 +1,191 @@
+// Hardware performance counters through Linux's `perf_event_open`, for
+// seeing why one strategy beats another: instructions, cycles, branch misses
+// and L1 data cache misses per call. Where the hardware counters can't be
+// opened, in most VMs for one, only the task clock is counted.
+
+/// The counters that can be asked for, by the names they're reported under.
+pub static EVENTS: &[&str] = &["instructions", "cycles", "branch_misses", "l1d_misses", "task_clock_ns"];
+
+/// A set of open counters.
+pub struct Counters {
+    counters: Vec<(&'static str, sys::Counter)>,
+}
+
+impl Counters {
+    /// Opens every counter the machine allows, or returns `None` if not even
+    /// the task clock can be opened.
+    pub fn open() -> Option<Counters> {
+        let counters: Vec<_> = EVENTS.iter()
+            .filter_map(|&name| sys::Counter::open(name).map(|c| (name, c)))
+            .collect();
+        if counters.is_empty() {
+            None
+        } else {
+            Some(Counters { counters: counters })
+        }
+    }
+
+    /// True if only the software counter could be opened.
+    pub fn software_only(&self) -> bool {
+        self.counters.iter().all(|&(name, _)| name == "task_clock_ns")
+    }
+
+    /// Counts what `iters` calls of `f` take, per call.
+    pub fn count<F: FnMut() -> R, R>(&mut self, iters: u64, mut f: F) -> Vec<(&'static str, f64)> {
+        for &mut (_, ref mut c) in &mut self.counters {
+            c.reset();
+        }
+        for &mut (_, ref mut c) in &mut self.counters {
+            c.enable();
+        }
+        for _ in 0..iters {
+            ::harness::black_box(f());
+        }
+        for &mut (_, ref mut c) in &mut self.counters {
+            c.disable();
+        }
+        self.counters.iter_mut()
+            .filter_map(|&mut (name, ref mut c)| c.read().map(|n| (name, n / iters.max(1) as f64)))
+            .collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::mem;
+
+    use libc;
+
+    // The first 112 bytes of `struct perf_event_attr`, which is all the
+    // kernel needs to be told about
+    #[repr(C)]
+    #[derive(Default)]
+    struct Attr {
+        kind: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+        config2: u64,
+        branch_sample_type: u64,
+        sample_regs_user: u64,
+        sample_stack_user: u32,
+        clockid: i32,
+        sample_regs_intr: u64,
+        aux_watermark: u32,
+        sample_max_stack: u16,
+        reserved: u16,
+    }
+
+    const TYPE_HARDWARE: u32 = 0;
+    const TYPE_SOFTWARE: u32 = 1;
+    const TYPE_HW_CACHE: u32 = 3;
+
+    const HW_CPU_CYCLES: u64 = 0;
+    const HW_INSTRUCTIONS: u64 = 1;
+    const HW_BRANCH_MISSES: u64 = 5;
+    const SW_TASK_CLOCK: u64 = 1;
+    // The cache (L1D = 0), the operation (read = 0 << 8) and the result
+    // (miss = 1 << 16)
+    const HW_CACHE_L1D_READ_MISS: u64 = 1 << 16;
+
+    const FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+    const FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+
+    const FLAG_DISABLED: u64 = 1 << 0;
+    const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+    const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+    const IOC_ENABLE: u64 = 0x2400;
+    const IOC_DISABLE: u64 = 0x2401;
+    const IOC_RESET: u64 = 0x2403;
+
+    pub struct Counter(libc::c_int);
+
+    impl Counter {
+        pub fn open(name: &str) -> Option<Counter> {
+            let (kind, config) = match name {
+                "instructions" => (TYPE_HARDWARE, HW_INSTRUCTIONS),
+                "cycles" => (TYPE_HARDWARE, HW_CPU_CYCLES),
+                "branch_misses" => (TYPE_HARDWARE, HW_BRANCH_MISSES),
+                "l1d_misses" => (TYPE_HW_CACHE, HW_CACHE_L1D_READ_MISS),
+                "task_clock_ns" => (TYPE_SOFTWARE, SW_TASK_CLOCK),
+                _ => return None,
+            };
+            let attr = Attr {
+                kind: kind,
+                size: mem::size_of::<Attr>() as u32,
+                config: config,
+                read_format: FORMAT_TOTAL_TIME_ENABLED | FORMAT_TOTAL_TIME_RUNNING,
+                flags: FLAG_DISABLED | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+                ..Attr::default()
+            };
+            // This thread, on any CPU, in no group
+            let fd = unsafe {
+                libc::syscall(libc::SYS_perf_event_open, &attr as *const Attr, 0, -1, -1, 0)
+            };
+            if fd < 0 {
+                None
+            } else {
+                Some(Counter(fd as libc::c_int))
+            }
+        }
+
+        fn ioctl(&mut self, request: u64) {
+            unsafe {
+                libc::ioctl(self.0, request as _, 0);
+            }
+        }
+
+        pub fn enable(&mut self) {
+            self.ioctl(IOC_ENABLE)
+        }
+
+        pub fn disable(&mut self) {
+            self.ioctl(IOC_DISABLE)
+        }
+
+        pub fn reset(&mut self) {
+            self.ioctl(IOC_RESET)
+        }
+
+        /// The count, scaled up for any time the kernel had the counter
+        /// switched out to share the hardware.
+        pub fn read(&mut self) -> Option<f64> {
+            let mut values = [0u64; 3];
+            let len = mem::size_of_val(&values);
+            let read = unsafe { libc::read(self.0, values.as_mut_ptr() as *mut libc::c_void, len) };
+            let (count, enabled, running) = (values[0], values[1], values[2]);
+            if read != len as isize || running == 0 {
+                return None;
+            }
+            Some(count as f64 * enabled as f64 / running as f64)
+        }
+    }
+
+    impl Drop for Counter {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.0);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    pub struct Counter;
+
+    impl Counter {
+        pub fn open(_: &str) -> Option<Counter> { None }
+        pub fn enable(&mut self) {}
+        pub fn disable(&mut self) {}
+        pub fn reset(&mut self) {}
+        pub fn read(&mut self) -> Option<f64> { None }
+    }
+}
@@ -0,0 +1,522 @@
+// Benchmark results as records that can be saved as JSON or CSV and compared
+// between runs. `src/bin/searchtest-bench.rs` is the command line for this.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+
+use baseline;
+use harness::{self, Config, Stats};
+use ladder;
+use manifest::Manifest;
+use matrix;
+use perf::Counters;
+
+/// One strategy timed over one input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub group: String,
+    pub strategy: String,
+    pub fixture: String,
+    pub size: usize,
+    /// The best vector extension the CPU has, from `accel_level`, which
+    /// isn't always the one the strategy ran at. Files saved before it was
+    /// named this call it `accel`.
+    pub cpu_accel: String,
+    pub ns_per_iter: f64,
+    pub stddev: f64,
+    /// GB per second.
+    pub throughput: f64,
+    /// Speed as a percentage of `baseline::SPEED_OF_LIGHT` over the same
+    /// number of bytes. NaN in results saved before this was recorded.
+    pub speed_of_light: f64,
+    pub cpu: String,
+    /// Per-call counts from `perf`, by the names in `perf::EVENTS`, if they
+    /// were asked for. They're saved after the other fields.
+    pub counters: Vec<(String, f64)>,
+}
+
+impl Record {
+    // What a record is compared by between runs
+    fn key(&self) -> (&str, &str, &str, usize) {
+        (&self.group, &self.strategy, &self.fixture, self.size)
+    }
+}
+
+static FIELDS: &[&str] = &[
+    "group", "strategy", "fixture", "size", "cpu_accel", "ns_per_iter", "stddev", "throughput",
+    "speed_of_light", "cpu",
+];
+
+/// The CPU's name as it gives it, or "unknown".
+pub fn cpu_model() -> String {
+    // `__cpuid` is only unsafe on older compilers
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[allow(unused_unsafe)]
+    unsafe {
+        use arch::__cpuid;
+
+        if __cpuid(0x8000_0000).eax >= 0x8000_0004 {
+            let mut brand = Vec::with_capacity(48);
+            for leaf in 0x8000_0002..0x8000_0005 {
+                let r = __cpuid(leaf);
+                for reg in &[r.eax, r.ebx, r.ecx, r.edx] {
+                    for i in 0..4 {
+                        brand.push((reg >> (i * 8)) as u8);
+                    }
+                }
+            }
+            let brand = String::from_utf8_lossy(&brand);
+            let brand = brand.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+            if !brand.is_empty() {
+                return brand.to_string();
+            }
+        }
+    }
+    "unknown".to_string()
+}
+
+/// The widest vector extension the strategies could use here.
+pub fn accel_level() -> &'static str {
+    #[cfg(simd)]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return "avx2";
+        } else if is_x86_feature_detected!("sse4.2") {
+            return "sse4.2";
+        } else if is_x86_feature_detected!("sse2") {
+            return "sse2";
+        }
+    }
+    "scalar"
+}
+
+/// Times every strategy of every group whose `group::strategy` name contains
+/// `filter`, handing each result to `progress` as it comes. With `sizes`,
+/// the strategies run over generated text of each size instead of their
+/// fixtures.
+///
+/// Each size of input also gets a record for each baseline, in a group
+/// called "baseline". With `counters`, every record also gets the counts of
+/// a separate run as long as the timed one.
+pub fn collect<F>(manifest: &Manifest, sizes: Option<&[usize]>, filter: &str, config: &Config,
+                  mut counters: Option<&mut Counters>, mut progress: F) -> Result<Vec<Record>, String>
+    where F: FnMut(&Record, &Stats)
+{
+    let cpu = cpu_model();
+    let cpu_accel = accel_level();
+    let mut records = Vec::new();
+    // The speed of light at each size timed so far
+    let mut speed_of_light = HashMap::new();
+
+    let record = |group: &str, strategy: &str, fixture: &str, size: usize, stats: &Stats, sol: f64| {
+        Record {
+            group: group.to_string(),
+            strategy: strategy.to_string(),
+            fixture: fixture.to_string(),
+            size: size,
+            cpu_accel: cpu_accel.to_string(),
+            ns_per_iter: stats.mean,
+            stddev: stats.stddev,
+            throughput: size as f64 / stats.mean,
+            speed_of_light: baseline::percent_of(sol, stats.mean),
+            cpu: cpu.clone(),
+            counters: Vec::new(),
+        }
+    };
+
+    for group in &manifest.groups {
+        let texts: Vec<(String, String)> = match sizes {
+            Some(sizes) => sizes.iter().map(|&size| ("generated".to_string(), ladder::text(group, size))).collect(),
+            None => {
+                let text = ::fixtures::fixture(&group.fixture)
+                    .ok_or_else(|| format!("no fixture named {}", group.fixture))?;
+                vec![(group.fixture.clone(), text.to_string())]
+            }
+        };
+
+        for strategy in &group.strategies {
+            if !format!("{}::{}", group.name, strategy).contains(filter) {
+                continue;
+            }
+            for (fixture, text) in &texts {
+                let mut run = match matrix::prepare_text(group, strategy, text)? {
+                    Some(run) => run,
+                    None => continue,
+                };
+                let expect = matrix::reference(&group.search, text);
+                let outcome = run();
+                if !outcome.satisfies(expect) {
+                    return Err(format!("{}::{} produced {:?} instead of {:?} over {} bytes",
+                                       group.name, strategy, outcome, expect, text.len()));
+                }
+
+                if let Entry::Vacant(entry) = speed_of_light.entry(text.len()) {
+                    // The text itself, not a zeroed buffer, whose pages all map to the
+                    // same physical zero page
+                    let bytes = text.as_bytes();
+                    let timed: Vec<_> = baseline::BASELINES.iter().map(|name| {
+                        let mut run = baseline::prepare(name, bytes).unwrap();
+                        let stats = harness::measure(config, &mut run);
+                        let counts = count(&mut counters, &stats, &mut run);
+                        (name, stats, counts)
+                    }).collect();
+                    let sol = timed.iter().find(|t| *t.0 == baseline::SPEED_OF_LIGHT).unwrap().1.mean;
+                    for (name, stats, counts) in timed {
+                        let mut r = record("baseline", name, fixture, text.len(), &stats, sol);
+                        r.counters = counts;
+                        progress(&r, &stats);
+                        records.push(r);
+                    }
+                    entry.insert(sol);
+                }
+
+                let stats = harness::measure(config, &mut run);
+                let mut r = record(&group.name, strategy, fixture, text.len(), &stats, speed_of_light[&text.len()]);
+                r.counters = count(&mut counters, &stats, &mut || { run(); });
+                progress(&r, &stats);
+                records.push(r);
+            }
+        }
+    }
+    Ok(records)
+}
+
+// Counts another run as long as the timed one
+fn count(counters: &mut Option<&mut Counters>, stats: &Stats, run: &mut dyn FnMut()) -> Vec<(String, f64)> {
+    match *counters {
+        Some(ref mut counters) => {
+            let iters = stats.iters_per_sample * stats.samples.len() as u64;
+            counters.count(iters, run).into_iter().map(|(name, n)| (name.to_string(), n)).collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+fn fields(r: &Record) -> Vec<(String, Value)> {
+    let values = vec![
+        Value::Str(r.group.clone()),
+        Value::Str(r.strategy.clone()),
+        Value::Str(r.fixture.clone()),
+        Value::Num(r.size as f64),
+        Value::Str(r.cpu_accel.clone()),
+        Value::Num(r.ns_per_iter),
+        Value::Num(r.stddev),
+        Value::Num(r.throughput),
+        Value::Num(r.speed_of_light),
+        Value::Str(r.cpu.clone()),
+    ];
+    FIELDS.iter().map(|name| name.to_string()).zip(values)
+        .chain(r.counters.iter().map(|&(ref name, n)| (name.clone(), Value::Num(n))))
+        .collect()
+}
+
+// Builds a record from the values of `names`, any of which that aren't
+// fields are counters
+fn from_fields<F>(names: &[String], mut get: F) -> Result<Record, String>
+    where F: FnMut(&str) -> Result<Value, String>
+{
+    let mut counters = Vec::new();
+    for name in names.iter().filter(|name| !FIELDS.contains(&&name[..]) && *name != "accel") {
+        match get(name)? {
+            Value::Str(ref s) if s.is_empty() => {}
+            value => counters.push((name.clone(), value.into_num()?)),
+        }
+    }
+
+    Ok(Record {
+        group: get("group")?.into_str()?,
+        strategy: get("strategy")?.into_str()?,
+        fixture: get("fixture")?.into_str()?,
+        size: get("size")?.into_num()? as usize,
+        cpu_accel: get("cpu_accel").or_else(|_| get("accel"))?.into_str()?,
+        ns_per_iter: get("ns_per_iter")?.into_num()?,
+        stddev: get("stddev")?.into_num()?,
+        throughput: get("throughput")?.into_num()?,
+        speed_of_light: get("speed_of_light").and_then(Value::into_num).unwrap_or(f64::NAN),
+        cpu: get("cpu")?.into_str()?,
+        counters: counters,
+    })
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Str(String),
+    Num(f64),
+}
+
+impl Value {
+    fn into_str(self) -> Result<String, String> {
+        match self {
+            Value::Str(s) => Ok(s),
+            Value::Num(n) => Ok(n.to_string()),
+        }
+    }
+
+    fn into_num(self) -> Result<f64, String> {
+        match self {
+            Value::Num(n) => Ok(n),
+            Value::Str(s) => s.parse().map_err(|_| format!("'{}' is not a number", s)),
+        }
+    }
+}
+
+/// Writes the records as a JSON array of objects, one per line.
+pub fn write_json<W: Write>(records: &[Record], mut out: W) -> io::Result<()> {
+    writeln!(out, "[")?;
+    for (i, record) in records.iter().enumerate() {
+        let mut line = String::from("  {");
+        for (j, (name, value)) in fields(record).into_iter().enumerate() {
+            if j > 0 {
+                line.push_str(", ");
+            }
+            let _ = write!(line, "\"{}\": ", name);
+            match value {
+                Value::Str(s) => json_string(&mut line, &s),
+                // JSON has no NaN or infinity
+                Value::Num(n) if !n.is_finite() => line.push_str("null"),
+                Value::Num(n) => { let _ = write!(line, "{}", n); }
+            }
+        }
+        line.push('}');
+        writeln!(out, "{}{}", line, if i + 1 < records.len() { "," } else { "" })?;
+    }
+    writeln!(out, "]")
+}
+
+fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Reads what `write_json` wrote. Only takes an array of flat objects of
+/// strings and numbers.
+pub fn parse_json(text: &str) -> Result<Vec<Record>, String> {
+    let mut p = JsonParser { rest: text };
+    let mut records = Vec::new();
+
+    p.expect('[')?;
+    if !p.eat(']') {
+        loop {
+            p.expect('{')?;
+            let mut object = Vec::new();
+            if !p.eat('}') {
+                loop {
+                    let name = p.string()?;
+                    p.expect(':')?;
+                    let value = if p.peek() == Some('"') {
+                        Value::Str(p.string()?)
+                    } else {
+                        Value::Num(p.number()?)
+                    };
+                    object.push((name, value));
+                    if p.eat('}') {
+                        break;
+                    }
+                    p.expect(',')?;
+                }
+            }
+            let names: Vec<String> = object.iter().map(|f| f.0.clone()).collect();
+            records.push(from_fields(&names, |name| {
+                object.iter().find(|f| f.0 == name).map(|f| f.1.clone())
+                    .ok_or_else(|| format!("record has no {}", name))
+            })?);
+            if p.eat(']') {
+                break;
+            }
+            p.expect(',')?;
+        }
+    }
+
+    if p.peek().is_some() {
+        return Err("trailing text after the records".to_string());
+    }
+    Ok(records)
+}
+
+struct JsonParser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> JsonParser<'a> {
+    fn peek(&mut self) -> Option<char> {
+        self.rest = self.rest.trim_start();
+        self.rest.chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.rest = &self.rest[1..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at '{}'", c, self.rest.chars().take(20).collect::<String>()))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(s);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'u')) => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16).ok().and_then(::std::char::from_u32)
+                            .ok_or_else(|| format!("bad escape '\\u{}'", hex))?;
+                        s.push(c);
+                    }
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, c)) => s.push(c),
+                    None => break,
+                },
+                c => s.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    // Takes `null`, which `write_json` writes for NaN and infinities, as NaN.
+    // Also takes the bare `NaN` and `inf` that older files have.
+    fn number(&mut self) -> Result<f64, String> {
+        self.peek();
+        let end = self.rest.find(|c: char| !(c.is_ascii_alphanumeric() || "+-.".contains(c)))
+            .unwrap_or(self.rest.len());
+        let n = match &self.rest[..end] {
+            "null" => f64::NAN,
+            word => word.parse().map_err(|_| format!("bad number '{}'", word))?,
+        };
+        self.rest = &self.rest[end..];
+        Ok(n)
+    }
+}
+
+/// Writes the records as CSV with a header line. Records without a counter
+/// that others have leave its cell empty.
+pub fn write_csv<W: Write>(records: &[Record], mut out: W) -> io::Result<()> {
+    let mut header: Vec<String> = FIELDS.iter().map(|name| name.to_string()).collect();
+    for record in records {
+        for (name, _) in &record.counters {
+            if !header.contains(name) {
+                header.push(name.clone());
+            }
+        }
+    }
+
+    writeln!(out, "{}", header.join(","))?;
+    for record in records {
+        let fields = fields(record);
+        let cells: Vec<String> = header.iter().map(|name| {
+            match fields.iter().find(|f| f.0 == *name).map(|f| &f.1) {
+                Some(Value::Str(s)) if s.contains([',', '"', '\n']) => {
+                    format!("\"{}\"", s.replace("\"", "\"\""))
+                }
+                Some(Value::Str(s)) => s.clone(),
+                Some(&Value::Num(n)) => n.to_string(),
+                None => String::new(),
+            }
+        }).collect();
+        writeln!(out, "{}", cells.join(","))?;
+    }
+    Ok(())
+}
+
+/// Reads what `write_csv` wrote. The columns can be in any order.
+pub fn parse_csv(text: &str) -> Result<Vec<Record>, String> {
+    let mut rows = csv_rows(text)?.into_iter();
+    let header = rows.next().ok_or("no header line")?;
+    let mut records = Vec::new();
+    for (i, cells) in rows.enumerate() {
+        records.push(from_fields(&header, |name| {
+            header.iter().position(|h| h == name)
+                .and_then(|col| cells.get(col))
+                .map(|cell| Value::Str(cell.clone()))
+                .ok_or_else(|| format!("row {} has no {}", i + 1, name))
+        })?);
+    }
+    Ok(records)
+}
+
+// The rows of cells, skipping blank lines. A quoted cell can span lines.
+fn csv_rows(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(::std::mem::take(&mut cell)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                cells.push(::std::mem::take(&mut cell));
+                if cells.len() > 1 || !cells[0].trim().is_empty() {
+                    rows.push(::std::mem::take(&mut cells));
+                }
+                cells.clear();
+            }
+            c => cell.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("unterminated quote in row {}", rows.len() + 1));
+    }
+    if !cells.is_empty() || !cell.trim().is_empty() {
+        cells.push(cell);
+        rows.push(cells);
+    }
+    Ok(rows)
+}
+
+/// One record that was in both runs.
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub old: Record,
+    pub new: Record,
+    /// New time over old time.
+    pub ratio: f64,
+    /// The new run is slower by more than the threshold.
+    pub regression: bool,
+}
+
+/// Pairs up the records of two runs. `threshold` is the fraction a time may
+/// grow by before it counts as a regression, so 0.05 allows 5%.
+pub fn compare(old: &[Record], new: &[Record], threshold: f64) -> Vec<Change> {
+    new.iter().filter_map(|new| {
+        let old = old.iter().find(|old| old.key() == new.key())?;
+        let ratio = new.ns_per_iter / old.ns_per_iter;
+        Some(Change {
+            old: old.clone(),
+            new: new.clone(),
+            ratio: ratio,
+            regression: ratio > 1.0 + threshold,
+        })
+    }).collect()
+}
@@ -0,0 +1,148 @@
+// The substring search strategies compared by the `find_*_substring_*`
+// benches.
+
+use jetscii::ByteSubstring;
+use memchr::memchr;
+use twoway;
+
+/// A searcher built for one needle.
+pub trait Searcher {
+    /// The offset of the first occurrence of the needle in `haystack`.
+    fn find(&self, haystack: &str) -> Option<usize>;
+}
+
+/// Builds a searcher for a non-empty needle.
+pub type Builder = for<'a> fn(&'a str) -> Box<dyn Searcher + 'a>;
+
+pub static STRATEGIES: &[(&str, Builder)] = &[
+    ("find_std", build_find_std),
+    ("jetscii", build_jetscii),
+    ("memchr", build_memchr),
+    #[cfg(feature = "nightly")]
+    ("memchr_std", build_memchr_std),
+    ("twoway", build_twoway),
+    ("bmh", build_bmh),
+];
+
+/// Answers whether the needle is in the haystack, without finding where.
+pub type Contains = fn(&str, &str) -> bool;
+
+/// The strategies that only say whether the needle is there. The manifest's
+/// cells check them against whether the group expects a position.
+pub static CONTAINS: &[(&str, Contains)] = &[
+    ("contains_std", contains_std),
+];
+
+fn contains_std(haystack: &str, needle: &str) -> bool {
+    haystack.contains(needle)
+}
+
+struct FindStd<'a>(&'a str);
+
+impl<'a> Searcher for FindStd<'a> {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        haystack.find(self.0)
+    }
+}
+
+fn build_find_std<'a>(needle: &'a str) -> Box<dyn Searcher + 'a> {
+    Box::new(FindStd(needle))
+}
+
+struct Jetscii<'a>(ByteSubstring<'a>);
+
+impl<'a> Searcher for Jetscii<'a> {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        self.0.find(haystack.as_bytes())
+    }
+}
+
+fn build_jetscii<'a>(needle: &'a str) -> Box<dyn Searcher + 'a> {
+    Box::new(Jetscii(ByteSubstring::new(needle.as_bytes())))
+}
+
+struct Memchr<'a>(&'a [u8]);
+
+impl<'a> Searcher for Memchr<'a> {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        find_memchr(haystack.as_bytes(), self.0)
+    }
+}
+
+fn build_memchr<'a>(needle: &'a str) -> Box<dyn Searcher + 'a> {
+    Box::new(Memchr(needle.as_bytes()))
+}
+
+#[cfg(feature = "nightly")]
+struct MemchrStd<'a>(&'a [u8]);
+
+#[cfg(feature = "nightly")]
+impl<'a> Searcher for MemchrStd<'a> {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        find_memchr_std(haystack.as_bytes(), self.0)
+    }
+}
+
+#[cfg(feature = "nightly")]
+fn build_memchr_std<'a>(needle: &'a str) -> Box<dyn Searcher + 'a> {
+    Box::new(MemchrStd(needle.as_bytes()))
+}
+
+struct TwoWay<'a>(&'a [u8]);
+
+impl<'a> Searcher for TwoWay<'a> {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        twoway::find_bytes(haystack.as_bytes(), self.0)
+    }
+}
+
+fn build_twoway<'a>(needle: &'a str) -> Box<dyn Searcher + 'a> {
+    Box::new(TwoWay(needle.as_bytes()))
+}
+
+struct Bmh<'a>(&'a [u8]);
+
+impl<'a> Searcher for Bmh<'a> {
+    fn find(&self, haystack: &str) -> Option<usize> {
+        twoway::bmh::find(haystack.as_bytes(), self.0)
+    }
+}
+
+fn build_bmh<'a>(needle: &'a str) -> Box<dyn Searcher + 'a> {
+    Box::new(Bmh(needle.as_bytes()))
+}
+
+/// Finds candidates for `needle` with `memchr` on its first byte and
+/// compares the rest.
+pub fn find_memchr(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(i) = memchr(needle[0], &haystack[offset..]) {
+        let start = offset + i;
+        if haystack.len() - start < needle.len() {
+            return None;
+        }
+        if &haystack[start..start + needle.len()] == needle {
+            return Some(start);
+        }
+        offset = start + 1;
+    }
+    None
+}
+
+/// Like `find_memchr`, with the standard library's internal `memchr`. Needs
+/// the `nightly` feature.
+#[cfg(feature = "nightly")]
+pub fn find_memchr_std(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(i) = ::core::slice::memchr::memchr(needle[0], &haystack[offset..]) {
+        let start = offset + i;
+        if haystack.len() - start < needle.len() {
+            return None;
+        }
+        if &haystack[start..start + needle.len()] == needle {
+            return Some(start);
+        }
+        offset = start + 1;
+    }
+    None
+}
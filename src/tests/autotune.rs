@@ -0,0 +1,105 @@
+// Round-trips the choices `autotune` saves through a file, checks that every
+// CPU key stays one field of a saved line and that a bad line only costs
+// its own CPU its choices, and that every byte set candidate is a strategy.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use autotune::{self, Choices, Level};
+use byteset;
+use matrix::lookup;
+
+fn temp_file(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("searchtest-{}-{}", name, process::id()))
+}
+
+// Levels every CPU has, so `load` never turns them down
+fn choices(byte_set: &'static str) -> Choices {
+    Choices { is_ascii: Level::Scalar, newlines: Level::Scalar, byte_set: byte_set }
+}
+
+#[test]
+fn vendors_with_spaces_make_one_field() {
+    // Zhaoxin, VIA, and a Skylake-SP signature for the family and model
+    for &vendor in &[&b"  Shanghai  "[..], b"VIA VIA VIA ", b"GenuineIntel"] {
+        let key = autotune::key_for(vendor, 0x0005_0654);
+        assert!(!key.contains(char::is_whitespace), "{:?}", key);
+        assert!(key.ends_with("-6-85"), "{:?}", key);
+
+        let line = format!("{} scalar sse2 open_table", key);
+        let (cpu, parsed) = autotune::parse_line(&line).unwrap();
+        assert_eq!(cpu, key);
+        assert_eq!(parsed, Choices { is_ascii: Level::Scalar, newlines: Level::Sse2, byte_set: "open_table" });
+    }
+    assert_eq!(autotune::key_for(b"  Shanghai  ", 0x0005_0654), "Shanghai-6-85");
+    assert_eq!(autotune::key_for(b"VIA VIA VIA ", 0x0005_0654), "VIA_VIA_VIA-6-85");
+}
+
+#[test]
+fn parse_line_rejects_bad_lines() {
+    assert!(autotune::parse_line("cpu scalar scalar").is_err());
+    assert!(autotune::parse_line("cpu scalar scalar open_table extra").is_err());
+    assert!(autotune::parse_line("cpu avx512 scalar open_table").is_err());
+    assert!(autotune::parse_line("cpu scalar scalar no_such_strategy").is_err());
+}
+
+#[test]
+fn save_then_load() {
+    let path = temp_file("autotune-round-trip");
+    let _ = fs::remove_file(&path);
+
+    assert_eq!(autotune::load(&path).unwrap(), None);
+    autotune::save(&path, &choices("open_table")).unwrap();
+    assert_eq!(autotune::load(&path).unwrap(), Some(choices("open_table")));
+
+    // Saving again replaces this CPU's line and keeps the others
+    let other = "OtherVendor-1-2 scalar scalar jetscii_bytes";
+    let text = fs::read_to_string(&path).unwrap();
+    fs::write(&path, format!("{}{}\n", text, other)).unwrap();
+    autotune::save(&path, &choices("position_table")).unwrap();
+    assert_eq!(autotune::load(&path).unwrap(), Some(choices("position_table")));
+
+    let text = fs::read_to_string(&path).unwrap();
+    let key = autotune::cpu_key();
+    let lines: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(lines.iter().filter(|l| l.split_whitespace().next() == Some(&key[..])).count(), 1, "{}", text);
+    assert!(lines.contains(&other), "{}", text);
+    for line in lines {
+        autotune::parse_line(line).unwrap();
+    }
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn bad_lines_are_no_choice() {
+    let path = temp_file("autotune-bad-lines");
+    let key = autotune::cpu_key();
+
+    // Another CPU's line, however broken, doesn't stop this one's loading
+    let good = format!("{} scalar scalar open_table", key);
+    fs::write(&path, format!("OtherVendor-1-2 avx512 ??\n{}\n", good)).unwrap();
+    assert_eq!(autotune::load(&path).unwrap(), Some(choices("open_table")));
+
+    // A broken line for this CPU is as good as none, so it gets tuned again
+    for bad in &["scalar scalar", "scalar scalar no_such_strategy", "avx512 scalar open_table"] {
+        fs::write(&path, format!("{} {}\n", key, bad)).unwrap();
+        assert_eq!(autotune::load(&path).unwrap(), None, "{}", bad);
+    }
+
+    // And saving over it leaves a good one
+    autotune::save(&path, &choices("position_table")).unwrap();
+    assert_eq!(autotune::load(&path).unwrap(), Some(choices("position_table")));
+
+    fs::remove_file(&path).unwrap();
+}
+
+// `measure` looks the candidates up by name, and skips any it can't find
+#[test]
+fn byte_set_candidates_are_strategies() {
+    for &name in autotune::BYTE_SET_STRATEGIES {
+        assert!(lookup(byteset::STRATEGIES, name).is_some(), "no byte set strategy named {}", name);
+    }
+}
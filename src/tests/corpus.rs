@@ -0,0 +1,67 @@
+// Checks what `corpus::generate` promises over a spread of specs: exactly
+// `size` bytes, the needle only where it was planted, and no non-ASCII
+// unless asked for.
+
+use byteset::ByteSet;
+use corpus::{self, Spec};
+
+fn specs() -> Vec<Spec> {
+    let mut specs = Vec::new();
+    for &size in &[0, 1, 3, 4, 5, 63, 64, 65, 1000] {
+        // Self-overlapping needles, and one that's all near misses but for its last byte
+        for needle in &["www.", "aa", "a", "abab", "xyz"] {
+            for &match_at in &[None, Some(0), Some(1), Some(size / 2), Some(size)] {
+                for &(near_miss_density, set_density, non_ascii_density) in
+                    &[(0.0, 0.0, 0.0), (0.5, 0.0, 0.0), (0.1, 0.3, 0.0), (0.1, 0.1, 0.3), (0.0, 0.0, 1.0)]
+                {
+                    specs.push(Spec {
+                        size: size,
+                        needle: needle.to_string(),
+                        match_at: match_at,
+                        near_miss_density: near_miss_density,
+                        set: ByteSet::new(b"a.w"),
+                        set_density: set_density,
+                        non_ascii_density: non_ascii_density,
+                        line_len: (1, 20),
+                    });
+                }
+            }
+        }
+    }
+    specs
+}
+
+fn occurrences(text: &str, needle: &str) -> Vec<usize> {
+    (0..text.len()).filter(|&i| text.as_bytes()[i..].starts_with(needle.as_bytes())).collect()
+}
+
+#[test]
+fn generate_keeps_its_promises() {
+    for spec in specs() {
+        for seed in 0..4 {
+            let text = corpus::generate(&spec, seed);
+            let what = || format!("{:?} at {:?} in {} bytes, densities {} {} {}, seed {}",
+                                  spec.needle, spec.match_at, spec.size, spec.near_miss_density,
+                                  spec.set_density, spec.non_ascii_density, seed);
+
+            assert_eq!(text.len(), spec.size, "{}", what());
+
+            let expected = match spec.match_at {
+                Some(at) if spec.needle.len() <= spec.size => vec![at.min(spec.size - spec.needle.len())],
+                _ => vec![],
+            };
+            assert_eq!(occurrences(&text, &spec.needle), expected, "{}", what());
+
+            if spec.non_ascii_density == 0.0 {
+                assert!(text.is_ascii(), "{}", what());
+            }
+            assert_eq!(text, corpus::generate(&spec, seed), "{}", what());
+        }
+    }
+}
+
+#[test]
+fn non_ascii_density_is_honoured() {
+    let spec = Spec { non_ascii_density: 0.5, ..Spec::default() };
+    assert!(!corpus::generate(&spec, 0).is_ascii());
+}
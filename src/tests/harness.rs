@@ -0,0 +1,72 @@
+// Checks the statistics `harness::measure` reports on fixed samples, where
+// the answers can be worked out by hand, and that it sizes its samples sanely
+// without a warmup.
+
+use std::time::Duration;
+
+use harness::{self, Config, Outliers};
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
+
+#[test]
+fn percentile_interpolates() {
+    let sorted = [1.0, 2.0, 4.0, 8.0, 16.0];
+    assert_eq!(harness::percentile(&sorted, 0.0), 1.0);
+    assert_eq!(harness::percentile(&sorted, 0.5), 4.0);
+    assert_eq!(harness::percentile(&sorted, 1.0), 16.0);
+    // A quarter of the way from 1.0 to 2.0, and three quarters from 8.0 to 16.0
+    assert!(close(harness::percentile(&sorted, 0.0625), 1.25));
+    assert!(close(harness::percentile(&sorted, 0.9375), 14.0));
+
+    assert_eq!(harness::percentile(&[7.0], 0.3), 7.0);
+    assert!(close(harness::percentile(&[10.0, 20.0], 0.25), 12.5));
+}
+
+#[test]
+fn outliers_use_tukeys_fences() {
+    // Quartiles 10.0 and 12.0, so the mild fences are 7.0 and 15.0 and the
+    // severe ones 4.0 and 18.0
+    let sorted = [1.0, 5.0, 10.0, 10.0, 10.0, 11.0, 12.0, 12.0, 12.0, 16.0, 30.0];
+    assert_eq!(harness::percentile(&sorted, 0.25), 10.0);
+    assert_eq!(harness::percentile(&sorted, 0.75), 12.0);
+    let outliers = harness::outliers(&sorted);
+    assert_eq!(outliers, Outliers { low_severe: 1, low_mild: 1, high_mild: 1, high_severe: 1 });
+    assert_eq!(outliers.total(), 4);
+
+    // On the fences isn't beyond them
+    assert_eq!(harness::outliers(&[7.0, 10.0, 11.0, 12.0, 15.0]).total(), 0);
+    assert_eq!(harness::outliers(&[3.0; 8]), Outliers::default());
+}
+
+#[test]
+fn bootstrap_brackets_the_mean() {
+    let samples: Vec<f64> = (0..40).map(|i| 100.0 + (i % 7) as f64).collect();
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+
+    let (low, high) = harness::bootstrap(&samples, 0.95, 1000);
+    assert!(low < mean && mean < high, "{} not in {:?}", mean, (low, high));
+    assert!(100.0 <= low && high <= 106.0, "{:?}", (low, high));
+    // Wider for more confidence, and the same every time
+    let (wide_low, wide_high) = harness::bootstrap(&samples, 0.999, 1000);
+    assert!(wide_low <= low && high <= wide_high);
+    assert_eq!(harness::bootstrap(&samples, 0.95, 1000), (low, high));
+
+    // Resampling identical samples can only give them back
+    assert_eq!(harness::bootstrap(&[5.0; 10], 0.95, 100), (5.0, 5.0));
+}
+
+#[test]
+fn measure_without_warmup_ends() {
+    let config = Config {
+        warmup: Duration::from_secs(0),
+        measurement: Duration::from_millis(1),
+        samples: 5,
+        ..Config::default()
+    };
+    let stats = harness::measure(&config, || ());
+    assert_eq!(stats.samples.len(), 5);
+    // No more than a nanosecond each would fill the time
+    assert!(stats.iters_per_sample <= 200_000, "{}", stats.iters_per_sample);
+}
@@ -0,0 +1,53 @@
+// Checks every is_ascii kernel against `<[u8]>::is_ascii` at every length up
+// to a few vectors, every start alignment within a cache line, and with a
+// single non-ASCII byte at every position.
+
+use super::super::*;
+use alignment::AlignedBuf;
+
+const MAX_LEN: usize = 256;
+const MAX_OFFSET: usize = 64;
+
+// The kernels this machine can run
+fn kernels() -> Vec<(&'static str, IsAscii)> {
+    is_ascii_strategies().into_iter()
+        .filter_map(|(name, kernel)| kernel.map(|kernel| (name, kernel)))
+        .collect()
+}
+
+fn check(kernels: &[(&'static str, IsAscii)], slice: &[u8], offset: usize, high: Option<usize>) {
+    let expected = slice.is_ascii();
+    for &(name, kernel) in kernels {
+        assert_eq!(kernel(slice), expected,
+                   "{} disagrees with <[u8]>::is_ascii at len {}, offset {}, non-ASCII byte at {:?}",
+                   name, slice.len(), offset, high);
+    }
+}
+
+#[test]
+fn all_lengths_and_offsets() {
+    let kernels = kernels();
+    // The bytes around the slice are non-ASCII, so reading past either
+    // end gives the wrong answer
+    let mut buf = AlignedBuf::new(MAX_OFFSET + MAX_LEN + MAX_OFFSET, 0xFF);
+
+    for len in 0..MAX_LEN + 1 {
+        for offset in 0..MAX_OFFSET {
+            let slice = &mut buf[offset..offset + len];
+            for byte in slice.iter_mut() {
+                *byte = b'a';
+            }
+            check(&kernels, slice, offset, None);
+
+            for pos in 0..len {
+                slice[pos] = 0x80;
+                check(&kernels, slice, offset, Some(pos));
+                slice[pos] = b'a';
+            }
+
+            for byte in slice.iter_mut() {
+                *byte = 0xFF;
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// Checks `LineIndex` against a model that works everything out from the text
+// on every call, over every short string of ASCII, wide chars and line
+// terminators, and by walking long generated text, which the vector newline
+// kernels see whole vectors of. Also checks that `edit` leaves the same index
+// as building a new one over the edited text.
+
+use super::super::*;
+use corpus::{self, Spec};
+
+// One char of each UTF-8 length, so both halves of a UTF-16 surrogate pair
+// turn up, and the line terminators
+const ALPHABET: &[char] = &['a', 'é', '€', '😀', '\r', '\n'];
+const MAX_LEN: usize = 4;
+const UNITS: &[ColumnUnit] = &[ColumnUnit::Byte, ColumnUnit::Char, ColumnUnit::Utf16];
+
+fn inputs(max_len: usize) -> Vec<String> {
+    let mut inputs = vec![String::new()];
+    let mut last = vec![String::new()];
+    for _ in 0..max_len {
+        last = last.iter()
+            .flat_map(|s| ALPHABET.iter().map(move |c| format!("{}{}", s, c)))
+            .collect();
+        inputs.extend(last.iter().cloned());
+    }
+    inputs
+}
+
+fn line_start(text: &str, offset: usize) -> usize {
+    text.as_bytes()[..offset].iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1)
+}
+
+fn reference_line_col(text: &str, offset: usize, unit: ColumnUnit) -> Option<LineCol> {
+    if offset > text.len() || (unit != ColumnUnit::Byte && !text.is_char_boundary(offset)) {
+        return None;
+    }
+    let line = text.as_bytes()[..offset].iter().filter(|&&b| b == b'\n').count();
+    let start = line_start(text, offset);
+    let col = match unit {
+        ColumnUnit::Byte => offset - start,
+        ColumnUnit::Char => text[start..offset].chars().count(),
+        ColumnUnit::Utf16 => text[start..offset].encode_utf16().count(),
+    };
+    Some(LineCol { line: line, col: col })
+}
+
+// The offset whose position is `line`, `col`, if there's one that doesn't
+// split a char
+fn reference_offset(text: &str, line: usize, col: usize, unit: ColumnUnit) -> Option<usize> {
+    (0..text.len() + 1)
+        .filter(|&offset| text.is_char_boundary(offset))
+        .find(|&offset| reference_line_col(text, offset, unit) == Some(LineCol { line: line, col: col }))
+}
+
+fn check(index: &LineIndex, text: &str) {
+    assert_eq!(index.len(), text.len(), "{:?}", text);
+    assert_eq!(index.line_count(), text.matches('\n').count() + 1, "{:?}", text);
+
+    for line in 0..index.line_count() + 1 {
+        let expected = if line < index.line_count() {
+            let start = text.split_inclusive('\n').take(line).map(str::len).sum::<usize>();
+            let end = text[start..].find('\n').map_or(text.len(), |i| start + i + 1);
+            Some(start..end)
+        } else {
+            None
+        };
+        assert_eq!(index.line_range(line), expected, "line {} of {:?}", line, text);
+
+        for &unit in UNITS {
+            for col in 0..text.len() + 2 {
+                assert_eq!(index.offset(line, col, unit), reference_offset(text, line, col, unit),
+                           "offset of {}:{} in {:?} units of {:?}", line, col, unit, text);
+            }
+        }
+    }
+
+    for offset in 0..text.len() + 2 {
+        for &unit in UNITS {
+            assert_eq!(index.line_col(offset, unit), reference_line_col(text, offset, unit),
+                       "line_col of {} in {:?} units of {:?}", offset, unit, text);
+        }
+    }
+}
+
+#[test]
+fn matches_reference() {
+    for text in inputs(MAX_LEN) {
+        check(&LineIndex::new(&text), &text);
+    }
+}
+
+#[test]
+fn known_positions() {
+    let text = "a\r\n😀é\r\n€";
+    let index = LineIndex::new(text);
+    assert_eq!(index.line_count(), 3);
+    assert_eq!(index.line_range(0), Some(0..3));
+    assert_eq!(index.line_col(1, ColumnUnit::Byte), Some(LineCol { line: 0, col: 1 }));
+    // After the emoji: 4 bytes, 1 char, 2 UTF-16 code units
+    assert_eq!(index.line_col(7, ColumnUnit::Byte), Some(LineCol { line: 1, col: 4 }));
+    assert_eq!(index.line_col(7, ColumnUnit::Char), Some(LineCol { line: 1, col: 1 }));
+    assert_eq!(index.line_col(7, ColumnUnit::Utf16), Some(LineCol { line: 1, col: 2 }));
+    // Inside the emoji
+    assert_eq!(index.line_col(5, ColumnUnit::Char), None);
+    assert_eq!(index.line_col(5, ColumnUnit::Utf16), None);
+    // Between the halves of its surrogate pair
+    assert_eq!(index.offset(1, 1, ColumnUnit::Utf16), None);
+    assert_eq!(index.offset(1, 2, ColumnUnit::Utf16), Some(7));
+    // Both halves of a CRLF are columns of the line, but nothing after them
+    assert_eq!(index.offset(1, 3, ColumnUnit::Utf16), Some(9));
+    assert_eq!(index.offset(1, 4, ColumnUnit::Utf16), Some(10));
+    assert_eq!(index.offset(1, 5, ColumnUnit::Utf16), None);
+    assert_eq!(index.offset(2, 1, ColumnUnit::Char), Some(text.len()));
+}
+
+fn assert_same(edited: &LineIndex, new: &LineIndex, text: &str, range: ::std::ops::Range<usize>, new_text: &str) {
+    let what = || format!("replacing {:?} of {:?} with {:?}", range, text, new_text);
+    assert_eq!(edited.len(), new.len(), "{}", what());
+    assert_eq!(edited.line_count(), new.line_count(), "{}", what());
+    for line in 0..new.line_count() {
+        assert_eq!(edited.line_range(line), new.line_range(line), "line {} {}", line, what());
+    }
+    for offset in 0..new.len() + 1 {
+        for &unit in UNITS {
+            assert_eq!(edited.line_col(offset, unit), new.line_col(offset, unit),
+                       "line_col of {} in {:?} units {}", offset, unit, what());
+        }
+    }
+}
+
+#[test]
+fn edit_matches_new() {
+    let replacements = ["", "a", "\n", "é\n😀", "\r\n\r\n", "€\n\n"];
+    for text in inputs(3) {
+        let boundaries: Vec<usize> = (0..text.len() + 1).filter(|&i| text.is_char_boundary(i)).collect();
+        for (i, &start) in boundaries.iter().enumerate() {
+            for &end in &boundaries[i..] {
+                for new_text in &replacements {
+                    let mut edited = text.clone();
+                    edited.replace_range(start..end, new_text);
+                    let mut index = LineIndex::new(&text);
+                    index.edit(start..end, new_text);
+                    assert_same(&index, &LineIndex::new(&edited), &text, start..end, new_text);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "splits a char")]
+fn edit_inside_a_char_panics() {
+    LineIndex::new("a€b").edit(2..4, "x");
+}
+
+// The model above takes quadratic time, so long text is checked by walking it
+// once, keeping the position of each char in every unit
+fn check_long(index: &LineIndex, text: &str) {
+    assert_eq!(index.len(), text.len());
+    assert_eq!(index.line_count(), text.matches('\n').count() + 1);
+
+    let (mut line, mut bytes, mut chars, mut utf16) = (0, 0, 0, 0);
+    for (offset, c) in text.char_indices().chain(Some((text.len(), '\0'))) {
+        for &(unit, col) in &[(ColumnUnit::Byte, bytes), (ColumnUnit::Char, chars), (ColumnUnit::Utf16, utf16)] {
+            let pos = LineCol { line: line, col: col };
+            assert_eq!(index.line_col(offset, unit), Some(pos), "line_col of {} in {:?} units", offset, unit);
+            assert_eq!(index.offset(line, col, unit), Some(offset), "offset of {:?} in {:?} units", pos, unit);
+        }
+        for inside in offset + 1..offset + c.len_utf8() {
+            assert_eq!(index.line_col(inside, ColumnUnit::Char), None, "line_col of {}", inside);
+        }
+
+        if c == '\n' {
+            assert_eq!(index.line_range(line).map(|range| range.end), Some(offset + 1), "end of line {}", line);
+            line += 1;
+            bytes = 0;
+            chars = 0;
+            utf16 = 0;
+        } else {
+            bytes += c.len_utf8();
+            chars += 1;
+            utf16 += c.len_utf16();
+        }
+    }
+    assert_eq!(index.line_range(line), Some(text.rfind('\n').map_or(0, |i| i + 1)..text.len()));
+}
+
+#[test]
+fn matches_reference_on_long_text() {
+    // Short lines, so every vector the newline kernels load holds some
+    for &(line_len, non_ascii_density) in &[((1, 4), 0.0), ((1, 40), 0.3), ((20, 100), 0.05)] {
+        for size in 1000..1040 {
+            let spec = Spec { size: size, line_len: line_len, non_ascii_density: non_ascii_density, ..Spec::default() };
+            let text = corpus::generate(&spec, size as u64);
+            check_long(&LineIndex::new(&text), &text);
+        }
+    }
+}
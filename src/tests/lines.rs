@@ -0,0 +1,156 @@
+// Runs every line splitter over every short string of `a`, `\r` and `\n`,
+// and compares it with a model of `str::lines`.
+
+use std::borrow::Cow;
+
+use super::super::*;
+use line_splitters::{Splitter, STRATEGIES};
+
+const ALPHABET: &[char] = &['a', '\r', '\n'];
+const MAX_LEN: usize = 8;
+
+fn inputs() -> Vec<String> {
+    let mut inputs = vec![String::new()];
+    let mut last = vec![String::new()];
+    for _ in 0..MAX_LEN {
+        last = last.iter()
+            .flat_map(|s| ALPHABET.iter().map(move |c| format!("{}{}", s, c)))
+            .collect();
+        inputs.extend(last.iter().cloned());
+    }
+    inputs
+}
+
+// Lines end at '\n', which takes a '\r' right before it along with it. Any
+// other '\r' is part of the line.
+fn reference_lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        match rest.find('\n') {
+            Some(i) => {
+                let line = &rest[..i];
+                if line.ends_with('\r') {
+                    lines.push(&line[..i - 1]);
+                } else {
+                    lines.push(line);
+                }
+                rest = &rest[i + 1..];
+            }
+            None => {
+                lines.push(rest);
+                rest = "";
+            }
+        }
+    }
+    lines
+}
+
+fn split(splitter: Splitter, text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    splitter(text, &mut |line| {
+        // Catch splitters that never make progress before they eat all memory
+        assert!(lines.len() <= text.len(), "more lines than bytes in {:?}", text);
+        lines.push(line.to_string());
+    });
+    lines
+}
+
+#[test]
+fn strategies_match_str_lines() {
+    let inputs = inputs();
+    let mut failures = Vec::new();
+
+    for &(name, splitter) in STRATEGIES {
+        let first = inputs.iter().filter_map(|text| {
+            let expected = reference_lines(text);
+            let actual = split(splitter, text);
+            if actual != expected {
+                Some(format!("{}: {:?} split into {:?}, expected {:?}", name, text, actual, expected))
+            } else {
+                None
+            }
+        }).next();
+        failures.extend(first);
+    }
+
+    assert!(failures.is_empty(), "first disagreements:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn fast_lines_methods_match_str_lines() {
+    for text in inputs() {
+        let expected = reference_lines(&text);
+
+        assert_eq!(fast_lines(&text).count(), expected.len(), "count of {:?}", text);
+        assert_eq!(count_lines(text.as_bytes()), expected.len(), "count_lines of {:?}", text);
+        assert_eq!(fast_lines(&text).last(), expected.last().cloned(), "last of {:?}", text);
+        for n in 0..expected.len() + 1 {
+            let mut lines = fast_lines(&text);
+            assert_eq!(lines.nth(n), expected.get(n).cloned(), "nth({}) of {:?}", n, text);
+            assert_eq!(lines.collect::<Vec<_>>(), expected.iter().skip(n + 1).cloned().collect::<Vec<_>>(),
+                       "lines after nth({}) of {:?}", n, text);
+        }
+
+        let bytes: Vec<&[u8]> = fast_byte_lines(text.as_bytes()).collect();
+        let expected_bytes: Vec<&[u8]> = expected.iter().map(|line| line.as_bytes()).collect();
+        assert_eq!(bytes, expected_bytes, "fast_byte_lines of {:?}", text);
+    }
+}
+
+// The vector kernel behind `normalize_line_endings` works 16 bytes at a time,
+// so the short strings are also run with `a`s in front, to land every `\r`
+// and `\n` at and across the end of a vector, and with `a`s after, to see
+// them both at and away from the end of the text.
+fn padded_inputs() -> Vec<String> {
+    let mut padded = Vec::new();
+    for text in inputs().into_iter().filter(|text| text.len() <= 4) {
+        for pad in 0..40 {
+            let text = format!("{}{}", "a".repeat(pad), text);
+            padded.push(format!("{}aaa", text));
+            padded.push(text);
+        }
+    }
+    padded
+}
+
+fn reference_normalize(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+#[test]
+fn normalize_line_endings_matches_reference() {
+    for text in padded_inputs() {
+        let expected = reference_normalize(&text);
+
+        let normalized = normalize_line_endings(&text);
+        assert_eq!(normalized, expected, "normalize_line_endings of {:?}", text);
+        if !text.contains('\r') {
+            assert!(matches!(normalized, Cow::Borrowed(_)), "copied {:?}", text);
+        }
+
+        let mut buf = text.clone().into_bytes();
+        normalize_line_endings_in_place(&mut buf);
+        assert_eq!(buf, expected.as_bytes(), "normalize_line_endings_in_place of {:?}", text);
+
+        // Without a lone `\r`, which `fast_lines` keeps in the line, the
+        // lines are the same
+        if !text.replace("\r\n", "").contains('\r') {
+            assert_eq!(fast_lines(&normalized).collect::<Vec<_>>(), fast_lines(&text).collect::<Vec<_>>(),
+                       "lines of {:?}", text);
+        }
+    }
+}
+
+#[test]
+fn lossy_lines_replace_only_bad_bytes() {
+    // Latin-1 é, ï and ñ, each a lone byte that UTF-8 has no use for, beside
+    // a real UTF-8 é and €
+    let text = b"plain\r\ncaf\xe9\nna\xefve \xc3\xa9\r\nse\xf1or\n\xe2\x82\xac";
+    let lines: Vec<Cow<str>> = fast_byte_lines(text).lossy().collect();
+    assert_eq!(lines, ["plain", "caf\u{fffd}", "na\u{fffd}ve é", "se\u{fffd}or", "€"]);
+
+    for (line, &valid) in lines.iter().zip(&[true, false, false, false, true]) {
+        assert_eq!(matches!(*line, Cow::Borrowed(_)), valid, "{:?}", line);
+    }
+}
@@ -0,0 +1,9 @@
+mod autotune;
+mod corpus;
+mod harness;
+mod is_ascii;
+mod line_index;
+mod lines;
+mod newlines;
+mod results;
+mod substring;
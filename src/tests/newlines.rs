@@ -0,0 +1,89 @@
+// Checks the newline kernels at every level this machine has against a plain
+// filter over the bytes, on buffers long enough for the vector loops, at
+// every length mod a vector and from unaligned starts. The bytes around each
+// buffer are `\n`, so reading past either end gives the wrong answer.
+
+use alignment::AlignedBuf;
+use autotune::{Level, NEWLINE_LEVELS};
+use newlines;
+use rng::Rng;
+
+// Long enough that each vector loop has to fold its lane counts more than
+// once before they overflow
+const LONG: usize = 2 * 255 * 32;
+const PAD: usize = 64;
+
+fn levels() -> Vec<Level> {
+    NEWLINE_LEVELS.iter().cloned().filter(|level| level.available()).collect()
+}
+
+// Every length mod 32 past `base`, and every start within a vector
+fn shapes(base: usize) -> Vec<(usize, usize)> {
+    let mut shapes = Vec::new();
+    for len in base..base + 33 {
+        for offset in 0..32 {
+            shapes.push((len, offset));
+        }
+    }
+    shapes
+}
+
+// `len` bytes at `offset` from a cache line, drawn from `bytes`, with `\n`
+// all around them
+fn buffer(rng: &mut Rng, bytes: &[u8], len: usize, offset: usize) -> AlignedBuf {
+    let mut buf = AlignedBuf::new(offset + len + PAD, b'\n');
+    for byte in &mut buf[offset..offset + len] {
+        *byte = *rng.pick(bytes);
+    }
+    buf
+}
+
+fn reference_count(buf: &[u8]) -> usize {
+    buf.iter().filter(|&&b| b == b'\n').count()
+}
+
+fn check_count(levels: &[Level], buf: &[u8], what: &str) {
+    let expected = reference_count(buf);
+    for &level in levels {
+        assert_eq!(unsafe { newlines::count_newlines_at(level, buf) }, expected,
+                   "{} at {:?}, len {}", what, level, buf.len());
+    }
+}
+
+#[test]
+fn count_newlines_matches_reference() {
+    let levels = levels();
+    let mut rng = Rng::new(0);
+    let mixed: &[u8] = b"\n\n\ra \xff";
+
+    for (len, offset) in shapes(0).into_iter().chain(shapes(LONG)) {
+        let buf = buffer(&mut rng, b"\n", len, offset);
+        check_count(&levels, &buf[offset..offset + len], &format!("all newlines from {}", offset));
+        let buf = buffer(&mut rng, mixed, len, offset);
+        check_count(&levels, &buf[offset..offset + len], &format!("mixed bytes from {}", offset));
+    }
+}
+
+fn reference_nth(buf: &[u8], n: usize) -> Option<usize> {
+    buf.iter().enumerate().filter(|&(_, &b)| b == b'\n').nth(n).map(|(i, _)| i)
+}
+
+#[test]
+fn nth_newline_matches_reference() {
+    let levels = levels();
+    let mut rng = Rng::new(0);
+
+    // A few newlines in every vector, so each `n` lands in the first block,
+    // a later one, the tail, or (past the last) nowhere
+    for (len, offset) in shapes(300) {
+        let buf = buffer(&mut rng, b"\naaaaaaaa\r", len, offset);
+        let buf = &buf[offset..offset + len];
+        for n in 0..reference_count(buf) + 2 {
+            let expected = reference_nth(buf, n);
+            for &level in &levels {
+                assert_eq!(unsafe { newlines::nth_newline_at(level, buf, n) }, expected,
+                           "newline {} at {:?}, len {} from {}", n, level, len, offset);
+            }
+        }
+    }
+}
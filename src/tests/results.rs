@@ -0,0 +1,127 @@
+// Round-trips records through both file formats, with the awkward strings
+// and counter columns the real ones have, reads the files older versions
+// wrote, and checks where `compare` draws the line for a regression.
+
+use std::f64;
+
+use results::{self, Record};
+
+fn record(strategy: &str, ns: f64, counters: &[(&str, f64)]) -> Record {
+    Record {
+        group: "find_short_substring_easy".to_string(),
+        strategy: strategy.to_string(),
+        fixture: "www.md".to_string(),
+        size: 4096,
+        cpu_accel: "avx2".to_string(),
+        ns_per_iter: ns,
+        stddev: 0.1 + ns / 3.0,
+        throughput: 4096.0 / ns,
+        speed_of_light: 87.5,
+        cpu: "Intel(R) Xeon(R) CPU @ 2.00GHz".to_string(),
+        counters: counters.iter().map(|&(name, n)| (name.to_string(), n)).collect(),
+    }
+}
+
+// Strings with everything either format has to escape
+fn records() -> Vec<Record> {
+    let mut quoted = record("jetscii", 12.25, &[("cycles", 40.5), ("instructions", 101.0)]);
+    quoted.cpu = "Vendor \"Quoted\", with commas, a \\ and a\nnewline".to_string();
+    quoted.fixture = "tab\there, \u{1} and ünïcödé 😀".to_string();
+    // Saved before the speed of light was, and without the counters the
+    // others have
+    let mut old = record("twoway", 1e-3, &[]);
+    old.speed_of_light = f64::NAN;
+    vec![record("find_std", 1234.5678, &[("cycles", 3.25)]), quoted, old, record("bmh", 7.0, &[])]
+}
+
+// Like `==`, but with NaN equal to itself
+fn assert_same(parsed: &[Record], written: &[Record]) {
+    assert_eq!(parsed.len(), written.len());
+    for (parsed, written) in parsed.iter().zip(written) {
+        let nan_free = |r: &Record| {
+            let mut r = r.clone();
+            assert_eq!(r.speed_of_light.is_nan(), written.speed_of_light.is_nan(), "{:?}", parsed);
+            if r.speed_of_light.is_nan() {
+                r.speed_of_light = 0.0;
+            }
+            r
+        };
+        assert_eq!(nan_free(parsed), nan_free(written));
+    }
+}
+
+#[test]
+fn json_round_trip() {
+    let records = records();
+    let mut out = Vec::new();
+    results::write_json(&records, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert_same(&results::parse_json(&text).unwrap(), &records);
+    // NaN is written as `null`, which other JSON readers take
+    assert!(text.contains("\"speed_of_light\": null") && !text.contains("NaN"), "{}", text);
+
+    assert_eq!(results::parse_json("[]").unwrap(), vec![]);
+    assert!(results::parse_json("[{\"group\": \"unterminated}]").is_err());
+    assert!(results::parse_json(&format!("{} trailing", text)).is_err());
+}
+
+#[test]
+fn csv_round_trip() {
+    let records = records();
+    let mut out = Vec::new();
+    results::write_csv(&records, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+
+    // A column for every counter any record has, after the fields
+    let header = text.lines().next().unwrap();
+    assert!(header.ends_with(",cycles,instructions"), "{}", header);
+
+    assert_same(&results::parse_csv(&text).unwrap(), &records);
+    // The same again with CRLF line endings
+    let crlf = text.replace(",cycles,instructions\n", ",cycles,instructions\r\n");
+    assert_same(&results::parse_csv(&crlf).unwrap(), &records);
+
+    assert!(results::parse_csv("").is_err());
+    assert!(results::parse_csv("group,strategy\n\"unterminated,x\n").is_err());
+}
+
+#[test]
+fn compare_threshold() {
+    let old = vec![record("find_std", 100.0, &[]), record("bmh", 100.0, &[]), record("gone", 1.0, &[])];
+    let new = vec![
+        // Exactly at the threshold still passes
+        record("find_std", 105.0, &[]),
+        // Just past it doesn't
+        record("bmh", 105.001, &[]),
+        // Only in the new run, so there's nothing to compare with
+        record("jetscii", 1000.0, &[]),
+    ];
+
+    let changes = results::compare(&old, &new, 0.05);
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].new.strategy, "find_std");
+    assert!(!changes[0].regression, "{:?}", changes[0]);
+    assert_eq!(changes[1].new.strategy, "bmh");
+    assert!(changes[1].regression, "{:?}", changes[1]);
+
+    // Getting faster is never a regression, even with no threshold at all
+    let changes = results::compare(&new, &old, 0.0);
+    assert!(changes.iter().all(|c| !c.regression && c.ratio < 1.0), "{:?}", changes);
+}
+
+// Saved before `accel` became `cpu_accel` and NaN became `null`
+#[test]
+fn reads_old_files() {
+    let json = "[\n  {\"group\": \"g\", \"strategy\": \"s\", \"fixture\": \"f\", \"size\": 16, \
+                \"accel\": \"sse2\", \"ns_per_iter\": 2, \"stddev\": inf, \"throughput\": 8, \
+                \"speed_of_light\": NaN, \"cpu\": \"c\"}\n]\n";
+    let csv = "group,strategy,fixture,size,accel,ns_per_iter,stddev,throughput,cpu\ng,s,f,16,sse2,2,inf,8,c\n";
+
+    for parsed in &[results::parse_json(json).unwrap(), results::parse_csv(csv).unwrap()] {
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].cpu_accel, "sse2");
+        assert_eq!(parsed[0].stddev, f64::INFINITY);
+        assert!(parsed[0].speed_of_light.is_nan());
+        assert!(parsed[0].counters.is_empty(), "{:?}", parsed[0].counters);
+    }
+}
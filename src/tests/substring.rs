@@ -0,0 +1,158 @@
+// Differential testing of the substring searchers against `str::find` on
+// random inputs. Set `SEARCHTEST_SEED` to start from another seed; each case
+// has its own seed, which a failure reports along with a minimised input.
+
+use std::env;
+use std::str;
+
+use rng::Rng;
+use substring::{Builder, STRATEGIES};
+
+const CASES: u64 = 20_000;
+const ALPHABET: &[u8] = b"abw.";
+
+fn gen_needle(rng: &mut Rng) -> Vec<u8> {
+    let alphabet = &ALPHABET[..rng.between(1, ALPHABET.len())];
+    let len = if rng.chance(0.1) { rng.between(17, 70) } else { rng.between(1, 16) };
+
+    if rng.chance(0.5) {
+        // Periodic needles are the hard case for two-way and BMH shifts
+        let period: Vec<u8> = (0..rng.between(1, 4)).map(|_| *rng.pick(alphabet)).collect();
+        let mut needle: Vec<u8> = period.iter().cycle().take(len).cloned().collect();
+        if rng.chance(0.5) {
+            needle[len - 1] = *rng.pick(ALPHABET);
+        }
+        needle
+    } else {
+        (0..len).map(|_| *rng.pick(alphabet)).collect()
+    }
+}
+
+fn gen_haystack(rng: &mut Rng, needle: &[u8]) -> Vec<u8> {
+    let len = rng.below(300);
+    let mut haystack = Vec::with_capacity(len + needle.len());
+
+    while haystack.len() < len {
+        match rng.below(5) {
+            0 => haystack.push(*rng.pick(ALPHABET)),
+            1 => {
+                // A near miss: a prefix of the needle, then anything
+                let prefix = rng.below(needle.len());
+                haystack.extend_from_slice(&needle[..prefix]);
+                haystack.push(*rng.pick(ALPHABET));
+            }
+            2 => {
+                let mut mutated = needle.to_vec();
+                let i = rng.below(mutated.len());
+                mutated[i] = *rng.pick(ALPHABET);
+                haystack.extend(mutated);
+            }
+            3 => {
+                // Repeats of a piece of the needle, which overlap it with
+                // itself at every shift
+                let piece = rng.between(1, needle.len());
+                for _ in 0..rng.between(1, 4) {
+                    haystack.extend_from_slice(&needle[..piece]);
+                }
+            }
+            _ => {
+                if rng.chance(0.3) {
+                    haystack.extend_from_slice(needle);
+                } else {
+                    haystack.push(*rng.pick(ALPHABET));
+                }
+            }
+        }
+    }
+
+    haystack
+}
+
+fn find(build: Builder, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let haystack = str::from_utf8(haystack).unwrap();
+    let needle = str::from_utf8(needle).unwrap();
+    build(needle).find(haystack)
+}
+
+fn disagrees(build: Builder, haystack: &[u8], needle: &[u8]) -> bool {
+    let expected = str::from_utf8(haystack).unwrap().find(str::from_utf8(needle).unwrap());
+    find(build, haystack, needle) != expected
+}
+
+// Removes ever smaller chunks of `seq` while `fails` still holds, then tries
+// turning each remaining byte into 'a'
+fn shrink<F: FnMut(&[u8]) -> bool>(seq: &mut Vec<u8>, min_len: usize, mut fails: F) -> bool {
+    let mut progress = false;
+
+    let mut chunk = seq.len();
+    while chunk > 0 {
+        let mut i = 0;
+        while i + chunk <= seq.len() && seq.len() - chunk >= min_len {
+            let mut candidate = seq.clone();
+            candidate.drain(i..i + chunk);
+            if fails(&candidate) {
+                *seq = candidate;
+                progress = true;
+            } else {
+                i += chunk;
+            }
+        }
+        chunk /= 2;
+    }
+
+    for i in 0..seq.len() {
+        if seq[i] != b'a' {
+            let mut candidate = seq.clone();
+            candidate[i] = b'a';
+            if fails(&candidate) {
+                *seq = candidate;
+                progress = true;
+            }
+        }
+    }
+
+    progress
+}
+
+fn minimize(build: Builder, mut haystack: Vec<u8>, mut needle: Vec<u8>) -> (String, String) {
+    loop {
+        let shrunk_haystack = {
+            let needle = &needle;
+            shrink(&mut haystack, 0, |h| disagrees(build, h, needle))
+        };
+        let shrunk_needle = {
+            let haystack = &haystack;
+            shrink(&mut needle, 1, |n| disagrees(build, haystack, n))
+        };
+        if !shrunk_haystack && !shrunk_needle {
+            break;
+        }
+    }
+
+    (String::from_utf8(haystack).unwrap(), String::from_utf8(needle).unwrap())
+}
+
+#[test]
+fn strategies_match_str_find() {
+    let base = env::var("SEARCHTEST_SEED").ok()
+        .map(|seed| seed.parse().expect("SEARCHTEST_SEED is not a number"))
+        .unwrap_or(0u64);
+
+    for seed in base..base + CASES {
+        let mut rng = Rng::new(seed);
+        let needle = gen_needle(&mut rng);
+        let haystack = gen_haystack(&mut rng, &needle);
+
+        for &(name, build) in STRATEGIES {
+            if disagrees(build, &haystack, &needle) {
+                let (haystack, needle) = minimize(build, haystack, needle);
+                panic!("{} disagrees with str::find (seed {}): found {:?} instead of {:?} \
+                        searching for {:?} in {:?}",
+                       name, seed,
+                       find(build, haystack.as_bytes(), needle.as_bytes()),
+                       haystack.find(&needle[..]),
+                       needle, haystack);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// The unchecked slicing and UTF-8 conversions the kernels make, in one place
+// so the `checked` feature can swap every one of them for its checked
+// version, for running the tests under Miri or a sanitizer. Without it, they
+// are still checked by debug assertions.
+
+use std::slice::SliceIndex;
+use std::str;
+
+/// `slice.get_unchecked(index)`.
+#[inline(always)]
+pub unsafe fn get<T, I: SliceIndex<[T]> + Clone>(slice: &[T], index: I) -> &I::Output {
+    if cfg!(feature = "checked") {
+        &slice[index]
+    } else {
+        debug_assert!(slice.get(index.clone()).is_some());
+        slice.get_unchecked(index)
+    }
+}
+
+/// `slice.get_unchecked_mut(index)`.
+#[inline(always)]
+pub unsafe fn get_mut<T, I: SliceIndex<[T]> + Clone>(slice: &mut [T], index: I) -> &mut I::Output {
+    if cfg!(feature = "checked") {
+        &mut slice[index]
+    } else {
+        debug_assert!(slice.get(index.clone()).is_some());
+        slice.get_unchecked_mut(index)
+    }
+}
+
+/// `text.get_unchecked(index)`.
+#[inline(always)]
+pub unsafe fn get_str<I: SliceIndex<str> + Clone>(text: &str, index: I) -> &I::Output {
+    if cfg!(feature = "checked") {
+        &text[index]
+    } else {
+        debug_assert!(text.get(index.clone()).is_some());
+        text.get_unchecked(index)
+    }
+}
+
+/// `str::from_utf8_unchecked(bytes)`.
+#[inline(always)]
+pub unsafe fn from_utf8(bytes: &[u8]) -> &str {
+    if cfg!(feature = "checked") {
+        str::from_utf8(bytes).expect("not UTF-8")
+    } else {
+        debug_assert!(str::from_utf8(bytes).is_ok());
+        str::from_utf8_unchecked(bytes)
+    }
+}
+
+/// `String::from_utf8_unchecked(bytes)`.
+#[inline(always)]
+pub unsafe fn string_from_utf8(bytes: Vec<u8>) -> String {
+    if cfg!(feature = "checked") {
+        String::from_utf8(bytes).expect("not UTF-8")
+    } else {
+        debug_assert!(str::from_utf8(&bytes).is_ok());
+        String::from_utf8_unchecked(bytes)
+    }
+}